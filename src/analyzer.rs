@@ -7,7 +7,7 @@ use std::path::Path;
 pub fn load_image(frame: usize) -> GrayImage {
     let filename = format!("video/frames/frame{}.jpg", frame);
     let path = Path::new(&filename);
-    open(&path).unwrap().to_luma8()
+    open(path).unwrap().to_luma8()
 }
 
 pub fn to_board(img: GrayImage) -> Board {
@@ -16,7 +16,6 @@ pub fn to_board(img: GrayImage) -> Board {
     Board::from_vec(
         (0..height)
             .rev()
-            .into_iter()
             .map(|h| {
                 data[(h * width)..(h * width + width)]
                     .iter()
@@ -26,3 +25,140 @@ pub fn to_board(img: GrayImage) -> Board {
             .collect(),
     )
 }
+
+/// Inclusive cell bounds, rows counted from the bottom like `Board`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Bounds {
+    pub min_row: usize,
+    pub max_row: usize,
+    pub min_col: usize,
+    pub max_col: usize,
+}
+
+impl Bounds {
+    fn around<'a>(cells: impl IntoIterator<Item = &'a (usize, usize)>) -> Option<Self> {
+        cells
+            .into_iter()
+            .fold(None, |bounds: Option<Self>, &(row, col)| {
+                Some(match bounds {
+                    None => Self {
+                        min_row: row,
+                        max_row: row,
+                        min_col: col,
+                        max_col: col,
+                    },
+                    Some(b) => Self {
+                        min_row: b.min_row.min(row),
+                        max_row: b.max_row.max(row),
+                        min_col: b.min_col.min(col),
+                        max_col: b.max_col.max(col),
+                    },
+                })
+            })
+    }
+
+    pub fn width(&self) -> usize {
+        self.max_col - self.min_col + 1
+    }
+
+    pub fn height(&self) -> usize {
+        self.max_row - self.min_row + 1
+    }
+}
+
+/// Cell-level changes from one target board to the next, as (row, col) pairs.
+#[derive(Clone, Debug, Default)]
+pub struct FrameDiff {
+    /// filled in the new frame only
+    pub added: Vec<(usize, usize)>,
+    /// filled in the old frame only
+    pub removed: Vec<(usize, usize)>,
+    /// filled in both
+    pub unchanged: Vec<(usize, usize)>,
+    pub cells: usize,
+}
+
+impl FrameDiff {
+    pub fn added_bounds(&self) -> Option<Bounds> {
+        Bounds::around(&self.added)
+    }
+
+    pub fn removed_bounds(&self) -> Option<Bounds> {
+        Bounds::around(&self.removed)
+    }
+
+    pub fn unchanged_bounds(&self) -> Option<Bounds> {
+        Bounds::around(&self.unchanged)
+    }
+
+    /// Everything that changed, added or removed.
+    pub fn changed_bounds(&self) -> Option<Bounds> {
+        Bounds::around(self.added.iter().chain(self.removed.iter()))
+    }
+
+    pub fn changed(&self) -> usize {
+        self.added.len() + self.removed.len()
+    }
+
+    /// Fraction of all cells that changed, from 0 to 1.
+    pub fn change_ratio(&self) -> f32 {
+        if self.cells == 0 {
+            0.0
+        } else {
+            self.changed() as f32 / self.cells as f32
+        }
+    }
+}
+
+pub fn frame_diff(before: &Board, after: &Board) -> FrameDiff {
+    let mut out = FrameDiff {
+        cells: after.width * after.height,
+        ..Default::default()
+    };
+    for row in 0..after.height {
+        for col in 0..after.width {
+            let was = row < before.height && col < before.width && before.get(row, col);
+            match (was, after.get(row, col)) {
+                (false, true) => out.added.push((row, col)),
+                (true, false) => out.removed.push((row, col)),
+                (true, true) => out.unchanged.push((row, col)),
+                (false, false) => {}
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_diff_sorts_cells_by_change() {
+        let mut before = Board::new(4, 3);
+        before.bulk_add(vec![(0, 0), (0, 1), (2, 3)]);
+        let mut after = Board::new(4, 3);
+        after.bulk_add(vec![(0, 1), (1, 2), (2, 2)]);
+
+        let diff = frame_diff(&before, &after);
+        assert_eq!(diff.added, vec![(1, 2), (2, 2)]);
+        assert_eq!(diff.removed, vec![(0, 0), (2, 3)]);
+        assert_eq!(diff.unchanged, vec![(0, 1)]);
+        assert_eq!(diff.change_ratio(), 4.0 / 12.0);
+
+        let changed = diff.changed_bounds().unwrap();
+        assert_eq!((changed.min_row, changed.max_row, changed.min_col, changed.max_col), (0, 2, 0, 3));
+        assert_eq!((changed.height(), changed.width()), (3, 4));
+        assert_eq!(diff.added_bounds().unwrap().width(), 1);
+    }
+
+    #[test]
+    fn a_smaller_old_frame_counts_as_empty() {
+        let mut after = Board::new(2, 2);
+        after.bulk_add(vec![(1, 1)]);
+        let diff = frame_diff(&Board::new(1, 1), &after);
+        assert_eq!(diff.added, vec![(1, 1)]);
+        assert!(diff.removed_bounds().is_none() && diff.unchanged_bounds().is_none());
+        assert_eq!(frame_diff(&after, &after).change_ratio(), 0.0);
+    }
+}
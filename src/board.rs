@@ -2,9 +2,14 @@
 use crate::piece::*;
 use std::fmt::{Display, Formatter};
 
-#[derive(Clone, Eq, PartialEq)]
+/// A full line removed by `Board::line_clear`, kept so the clear can be undone.
+pub type Line = (Vec<bool>, Vec<Option<usize>>);
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Board {
     pub arr: Vec<Vec<bool>>,
+    /// which piece filled each cell, when known
+    pub kinds: Vec<Vec<Option<usize>>>,
     pub width: usize,
     pub height: usize,
 }
@@ -13,12 +18,17 @@ impl Board {
     pub fn from_vec(arr: Vec<Vec<bool>>) -> Self {
         let height = arr.len();
         let width = arr[0].len();
-        Self { arr, width, height }
+        let kinds = vec![vec![None; width]; height];
+        Self {
+            arr,
+            kinds,
+            width,
+            height,
+        }
     }
 
     pub fn new(width: usize, height: usize) -> Self {
-        let arr = vec![vec![false; width]; height];
-        Self { arr, width, height }
+        Self::from_vec(vec![vec![false; width]; height])
     }
 
     pub fn bulk_add(&mut self, points: Vec<(usize, usize)>) {
@@ -28,31 +38,73 @@ impl Board {
     }
 
     pub fn get(&self, row: usize, col: usize) -> bool {
-        if row > 40 || col > 40 {
+        if row >= self.height || col >= self.width {
             false
         } else {
             self.arr[row][col]
         }
     }
 
+    pub fn kind(&self, row: usize, col: usize) -> Option<usize> {
+        if self.get(row, col) {
+            self.kinds[row][col]
+        } else {
+            None
+        }
+    }
+
     pub fn add(&mut self, row: usize, col: usize) {
         self.arr[row][col] = true;
+        self.kinds[row][col] = None;
+    }
+
+    pub fn paint(&mut self, row: usize, col: usize, kind: usize) {
+        self.arr[row][col] = true;
+        self.kinds[row][col] = Some(kind);
     }
 
     pub fn remove(&mut self, row: usize, col: usize) {
         self.arr[row][col] = false;
+        self.kinds[row][col] = None;
     }
 
-    pub fn line_clear(&mut self, row: usize) -> Option<Vec<bool>> {
-        self.arr.push(vec![false; self.width]);
-
+    pub fn line_clear(&mut self, row: usize) -> Option<Line> {
         if self.arr[row].iter().all(|&x| x) {
-            Some(self.arr.remove(row))
+            self.arr.push(vec![false; self.width]);
+            self.kinds.push(vec![None; self.width]);
+            Some((self.arr.remove(row), self.kinds.remove(row)))
         } else {
             None
         }
     }
 
+    /// Puts back a line taken by `line_clear`, dropping the empty top row.
+    pub fn restore_line(&mut self, row: usize, (cells, kinds): Line) {
+        self.arr.pop();
+        self.kinds.pop();
+        self.arr.insert(row, cells);
+        self.kinds.insert(row, kinds);
+    }
+
+    /// Pushes `line` in at the bottom, lifting everything else a row, and
+    /// returns the top row that got pushed off.
+    pub fn insert_bottom(&mut self, (cells, kinds): Line) -> Line {
+        self.arr.insert(0, cells);
+        self.kinds.insert(0, kinds);
+        (self.arr.pop().unwrap(), self.kinds.pop().unwrap())
+    }
+
+    /// Undoes `insert_bottom`, putting `top` back and returning the bottom row.
+    pub fn remove_bottom(&mut self, (cells, kinds): Line) -> Line {
+        self.arr.push(cells);
+        self.kinds.push(kinds);
+        (self.arr.remove(0), self.kinds.remove(0))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arr.iter().all(|row| row.iter().all(|&cell| !cell))
+    }
+
     pub fn piece_collision(&self, piece: &Placement) -> bool {
         piece
             .rel_locations()
@@ -84,6 +136,14 @@ impl Board {
         self.piece_valid_location(piece) && self.piece_grounded(piece)
     }
 
+    pub fn ghost(&self, piece: &Placement) -> Placement {
+        let mut out = *piece;
+        while !self.piece_grounded(&out) {
+            out.shift(-1, 0);
+        }
+        out
+    }
+
     fn in_bounds(&self, row: usize, col: usize) -> bool {
         row < self.width && col < self.height
     }
@@ -102,7 +162,7 @@ impl Board {
                     out.push_str("□ ");
                 }
             }
-            out.push_str("\n");
+            out.push('\n');
         }
         out
     }
@@ -118,8 +178,43 @@ impl Display for Board {
                     write!(f, "□ ")?
                 }
             }
-            write!(f, "\n")?
+            writeln!(f)?
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_clear_keeps_the_height() {
+        let mut board = Board::new(4, 6);
+        board.add(1, 2);
+        assert!(board.line_clear(1).is_none());
+        assert_eq!(board.arr.len(), 6);
+
+        for col in 0..4 {
+            board.add(0, col);
+        }
+        let before = board.clone();
+        let line = board.line_clear(0).unwrap();
+        assert_eq!(board.arr.len(), 6);
+        assert!(board.get(0, 2));
+        board.restore_line(0, line);
+        assert_eq!(board, before);
+    }
+
+    #[test]
+    fn reads_past_the_edge_are_empty() {
+        let mut board = Board::new(10, 20);
+        board.paint(19, 9, 6);
+        assert!(board.get(19, 9));
+        assert_eq!(board.kind(19, 9), Some(6));
+        for (row, col) in [(20, 0), (0, 10), (39, 39), (usize::MAX, 0)] {
+            assert!(!board.get(row, col));
+            assert_eq!(board.kind(row, col), None);
+        }
+    }
+}
@@ -4,10 +4,11 @@ use crate::board::Board;
 use crate::control::*;
 use crate::game::*;
 use crate::piece::Placement;
-use itertools::Itertools;
-use std::cmp::Ordering;
-use std::collections::{HashSet, VecDeque};
+use crate::plan::*;
+use crate::viewport::Viewport;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::{Debug, Display, Formatter};
+use std::time::{Duration, Instant};
 
 pub fn score(pieces: &Vec<Placement>, board: &Board) -> i8 {
     let mut out = 0;
@@ -25,15 +26,69 @@ pub fn score(pieces: &Vec<Placement>, board: &Board) -> i8 {
     out
 }
 
-fn sort(board: Board) -> Box<dyn Fn(&PlacementActions, &PlacementActions) -> Ordering> {
-    Box::new(move |a, b|
-        score(&a.placement, &board).partial_cmp(&score(&b.placement, &board)).unwrap()
-    )
+/// Like `score`, but only cells inside `viewport` count, so pieces above or
+/// beside the picture are neither rewarded nor punished.
+pub fn score_in(pieces: &Vec<Placement>, board: &Board, viewport: &Viewport) -> i8 {
+    let mut out = 0;
+    for piece in pieces {
+        for [row, col] in piece.abs_locations() {
+            let (row, col) = (row as usize, col as usize);
+            if !viewport.contains(row, col) {
+                continue;
+            }
+            if board.get(row, col) {
+                out -= 1;
+            } else {
+                out += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Budget for `Bot::anytime_search`; `None` leaves that dimension unbounded.
+#[derive(Copy, Clone, Debug)]
+pub struct SearchLimits {
+    pub max_depth: usize,
+    /// counted from when the search starts, not when the limits are built
+    pub time: Option<Duration>,
+    pub max_nodes: Option<usize>,
+}
+
+impl SearchLimits {
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            time: None,
+            max_nodes: None,
+        }
+    }
+
+    pub fn time(mut self, budget: Duration) -> Self {
+        self.time = Some(budget);
+        self
+    }
+
+    pub fn nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct SearchReport {
+    pub best: Option<Plan>,
+    pub depth: usize,
+    pub nodes: usize,
 }
 
 pub struct Bot {
     pub game: Game,
+    pub seed: usize,
     stack: VecDeque<Command>,
+    limits: Option<SearchLimits>,
+    deadline: Option<Instant>,
+    nodes: usize,
 }
 
 impl Display for Bot {
@@ -47,11 +102,25 @@ impl Bot {
     pub fn new(height: usize, width: usize, seed: usize) -> Self {
         Self {
             game: Game::new(height, width, seed),
+            seed,
             stack: VecDeque::new(),
+            limits: None,
+            deadline: None,
+            nodes: 0,
+        }
+    }
+
+    fn out_of_budget(&self) -> bool {
+        match self.limits {
+            None => false,
+            Some(limits) => {
+                limits.max_nodes.is_some_and(|max| self.nodes >= max)
+                    || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+            }
         }
     }
 
-    fn unfiltered_search(&mut self, base: &mut PlacementActions, used: &mut HashSet<PlacementActions>) {
+    fn unfiltered_search(&mut self, base: &mut PlacementActions, used: &mut HashMap<Placement, PlacementActions>) {
         let commands: Vec<Command> = vec![
             PieceMove::new(0, -1).into(),
             PieceMove::new(0, 1).into(),
@@ -62,11 +131,15 @@ impl Bot {
         ];
 
         for command in commands {
-            if self.action(command.clone()) && !duplicate_placement(&used, &self.game.active) {
+            if self.out_of_budget() {
+                return;
+            }
+            self.nodes += 1;
+            if self.action(command.clone()) && !used.contains_key(&self.game.active) {
                 base.push(command);
                 base.placement.pop();
                 base.placement.push(self.game.active);
-                used.insert(base.clone());
+                used.insert(self.game.active, base.clone());
                 self.unfiltered_search(base, used);
                 base.pop();
             }
@@ -74,8 +147,8 @@ impl Bot {
         }
     }
 
-    fn search(&mut self, sort_predicate: Box<dyn Fn(&PlacementActions, &PlacementActions) -> Ordering>, n: usize) -> HashSet<PlacementActions> {
-        let mut used = HashSet::new();
+    fn search(&mut self) -> HashSet<PlacementActions> {
+        let mut used = HashMap::new();
         let mut empty = PlacementActions::new();
         let mut hold = PlacementActions::new();
         hold.push(Hold::new().into());
@@ -86,50 +159,149 @@ impl Bot {
         self.unfiltered_search(&mut hold, &mut used);
         self.undo();
 
-        used.into_iter()
-            .filter(|placement| self.game.board.piece_valid_placement(&placement.placement.last().unwrap()))
-            .sorted_by(sort_predicate)
-            .take(n)
+        used.into_values()
+            .filter(|placement| self.game.board.piece_valid_placement(placement.placement.last().unwrap()))
             .collect()
     }
 
-    fn deep_search(&mut self, depth: usize, mut base: PlacementActions, n: usize, board: &Board) -> HashSet<PlacementActions> {
-        base.execute_last(&mut self.game);
-        let mut search = self.search(sort(board.clone()), n);
-        search = search.into_iter().map(
-            |placement| placement.ret_push_back(HardDrop::new().into())
-        ).collect();
+    /// Every grounded placement of the active piece, or of the one hold
+    /// swaps in, in a fixed order. None of them end in a hard drop yet.
+    pub fn placements(&mut self) -> Vec<PlacementActions> {
+        let mut out: Vec<PlacementActions> = self.search().into_iter().collect();
+        out.sort_by_key(|actions| {
+            let p = actions.placement.last().unwrap();
+            let hold = matches!(actions.batch.commands.front(), Some(Command::Hold(_)));
+            (hold, p.piece_type, p.rotation_state, p.row, p.col)
+        });
+        out
+    }
+
+    /// Plays `actions`, which must end in a hard drop, and records the step
+    /// it makes. `actions.undo` takes it back.
+    pub fn step(&mut self, actions: &mut PlacementActions) -> Step {
+        let placement = *actions.placement.last().unwrap();
+        let hold = matches!(actions.batch.commands.front(), Some(Command::Hold(_)));
+        let inputs = actions.batch.commands.iter().cloned().collect();
 
-        if depth == 1 {
-            base.undo_last(&mut self.game);
-            return search
-                .into_iter()
-                .map(|action| base.ret_push_back(action.into()))
-                .collect();
+        actions.execute(&mut self.game);
+        let lines_cleared = match actions.batch.commands.back() {
+            Some(Command::HardDrop(drop)) => drop.lines_cleared(),
+            _ => 0,
+        };
+        Step {
+            placement,
+            inputs,
+            hold,
+            lines_cleared,
+            board: self.game.board.clone(),
         }
+    }
 
-        let mut out = HashSet::new();
-        for action in search {
-            let base = base.ret_push_back(action.into());
-            out.extend(self.deep_search(depth - 1, base, n, board));
+    fn deep_search(&mut self, depth: usize, base: &mut Plan, n: usize, board: &Board, out: &mut Vec<Plan>) {
+        let eval = |step: &Step| score(&vec![step.placement], board) as i32;
+        self.deep_search_by(depth, base, n, &eval, out)
+    }
+
+    fn deep_search_by(&mut self, depth: usize, base: &mut Plan, n: usize, eval: &dyn Fn(&Step) -> i32, out: &mut Vec<Plan>) {
+        // every placement has to be played out to be evaluated
+        let mut candidates = Vec::new();
+        for mut actions in self.search() {
+            actions.push(HardDrop::new().into());
+            let value = eval(&self.step(&mut actions));
+            actions.undo(&mut self.game);
+            candidates.push((value, actions));
+        }
+        // ties broken on the placement, so the same position always gives the same plan
+        candidates.sort_by_key(|(value, actions)| {
+            let p = actions.placement.last().unwrap();
+            (*value, p.piece_type, p.rotation_state, p.row, p.col)
+        });
+
+        for (value, mut actions) in candidates.into_iter().take(n) {
+            let step = self.step(&mut actions);
+            base.evaluation += value;
+            base.steps.push(step);
+
+            if depth == 1 {
+                out.push(base.clone());
+            } else {
+                self.deep_search_by(depth - 1, base, n, eval, out);
+            }
+
+            base.steps.pop();
+            base.evaluation -= value;
+            actions.undo(&mut self.game);
         }
+    }
 
-        base.undo_last(&mut self.game);
+    pub fn look_ahead(&mut self, depth: usize, n: usize, board: &Board) -> Vec<Plan> {
+        let mut out = Vec::new();
+        self.deep_search(depth, &mut Plan::new(), n, board, &mut out);
         out
     }
 
-    pub fn look_ahead(&mut self, depth: usize, n: usize, board: &Board) -> HashSet<PlacementActions> {
-        self.deep_search(depth, PlacementActions::new(), n, board)
+    fn pick(plans: Vec<Plan>) -> Option<Plan> {
+        plans.into_iter().min_by_key(|plan| plan.evaluation)
+    }
+
+    pub fn best_plan(&mut self, depth: usize, n: usize, board: &Board) -> Option<Plan> {
+        Self::pick(self.look_ahead(depth, n, board))
     }
 
-    pub fn best_action(&mut self, depth: usize, n: usize, board: &Board) -> Command {
-        self.look_ahead(depth, n, board)
-            .into_iter()
-            .sorted_by(sort(board.clone()))
-            .next()
-            .unwrap()
-            .pop_front()
-            .unwrap()
+    /// Like `best_plan`, for a `board` laid out on the whole playfield of
+    /// which only `viewport` is scored.
+    pub fn best_plan_in(&mut self, depth: usize, n: usize, board: &Board, viewport: &Viewport) -> Option<Plan> {
+        let eval = |step: &Step| score_in(&vec![step.placement], board, viewport) as i32;
+        self.best_plan_by(depth, n, &eval)
+    }
+
+    /// Like `look_ahead`, but ranks each step with `eval` instead of against
+    /// a target board. Lower is better, and a plan's evaluation is the sum
+    /// over its steps.
+    pub fn look_ahead_by(&mut self, depth: usize, n: usize, eval: &dyn Fn(&Step) -> i32) -> Vec<Plan> {
+        let mut out = Vec::new();
+        self.deep_search_by(depth, &mut Plan::new(), n, eval, &mut out);
+        out
+    }
+
+    pub fn best_plan_by(&mut self, depth: usize, n: usize, eval: &dyn Fn(&Step) -> i32) -> Option<Plan> {
+        Self::pick(self.look_ahead_by(depth, n, eval))
+    }
+
+    pub fn best_action(&mut self, depth: usize, n: usize, board: &Board) -> Option<Command> {
+        self.best_plan(depth, n, board)
+            .and_then(|plan| plan.first().map(Step::command))
+    }
+
+    /// Iterative deepening over `look_ahead` that stops once `limits` runs out.
+    /// The deepest completed iteration supplies the move; an interrupted depth 1
+    /// still yields whatever it managed to find.
+    pub fn anytime_search(&mut self, limits: SearchLimits, n: usize, board: &Board) -> SearchReport {
+        self.limits = Some(limits);
+        self.deadline = limits.time.map(|budget| Instant::now() + budget);
+        self.nodes = 0;
+
+        let mut report = SearchReport {
+            best: None,
+            depth: 0,
+            nodes: 0,
+        };
+        for depth in 1..=limits.max_depth {
+            let plans = self.look_ahead(depth, n, board);
+            if self.out_of_budget() {
+                if report.best.is_none() {
+                    report.best = Self::pick(plans);
+                }
+                break;
+            }
+            report.best = Self::pick(plans);
+            report.depth = depth;
+        }
+
+        report.nodes = self.nodes;
+        self.limits = None;
+        self.deadline = None;
+        report
     }
 
     // pub fn build_pattern(&mut self, board: &Board) {
@@ -165,6 +337,11 @@ impl Bot {
     //     }
     // }
 
+    /// Every command still on the undo stack, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Command> {
+        self.stack.iter().rev()
+    }
+
     pub fn undo(&mut self) {
         self.stack.pop_front().unwrap().undo(&mut self.game)
     }
@@ -177,10 +354,26 @@ impl Bot {
         self.action(PieceMove::new(0, 1).into())
     }
 
+    pub fn das_left(&mut self) -> bool {
+        self.action(DasShift::new(-1).into())
+    }
+
+    pub fn das_right(&mut self) -> bool {
+        self.action(DasShift::new(1).into())
+    }
+
     pub fn soft_drop(&mut self) -> bool {
         self.action(SoftDrop::new().into())
     }
 
+    pub fn soft_drop_by(&mut self, rows: i8) -> bool {
+        self.action(PartialSoftDrop::new(rows).into())
+    }
+
+    pub fn sonic_drop(&mut self) -> bool {
+        self.action(SonicDrop::new().into())
+    }
+
     pub fn rotate_cw(&mut self) -> bool {
         self.action(PieceRotate::new(1).into())
     }
@@ -201,8 +394,85 @@ impl Bot {
         self.action(Hold::new().into())
     }
 
+    pub fn add_garbage(&mut self, holes: Vec<usize>) -> bool {
+        self.action(AddGarbage::new(holes).into())
+    }
+
+    pub fn play(&mut self, step: &Step) -> bool {
+        self.action(step.command())
+    }
+
     pub fn action(&mut self, command: Command) -> bool {
         self.stack.push_front(command);
         self.stack.get_mut(0).unwrap().execute(&mut self.game)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    fn target() -> Board {
+        let mut board = Board::new(10, 20);
+        for col in 0..4 {
+            board.add(0, col);
+        }
+        board
+    }
+
+    #[test]
+    fn time_budget_starts_with_the_search() {
+        let limits = SearchLimits::new(1).time(Duration::from_millis(200));
+        sleep(Duration::from_millis(300));
+
+        let mut bot = Bot::new(20, 10, 1);
+        let report = bot.anytime_search(limits, 3, &target());
+        assert_eq!(report.depth, 1);
+        assert!(report.best.is_some());
+    }
+
+    #[test]
+    fn node_budget_stops_deepening() {
+        let mut bot = Bot::new(20, 10, 1);
+        let report = bot.anytime_search(SearchLimits::new(3).nodes(2000), 3, &target());
+        assert!(report.depth < 3, "{}", report.depth);
+        assert!(report.best.is_some());
+        assert!(report.nodes <= 2000);
+        // the search leaves the game as it found it
+        assert!(bot.game.board.is_empty());
+        assert!(bot.history().next().is_none());
+    }
+
+    #[test]
+    fn best_plan_paints_the_target() {
+        let mut bot = Bot::new(20, 10, 1);
+        let target = target();
+        let plan = bot.best_plan(2, 4, &target).unwrap();
+        let by_score = bot
+            .best_plan_by(2, 4, &|step| score(&vec![step.placement], &target) as i32)
+            .unwrap();
+        assert_eq!(plan.evaluation, by_score.evaluation);
+        assert_eq!(plan.first().map(|step| step.placement), by_score.first().map(|step| step.placement));
+        // the O can only half cover the row, so hold brings in the J to lie on it
+        assert!(plan.steps[0].hold);
+        assert_eq!(score(&vec![plan.steps[0].placement], &target), -2);
+        let total: i32 = plan.steps.iter().map(|step| score(&vec![step.placement], &target) as i32).sum();
+        assert_eq!(plan.evaluation, total);
+    }
+
+    #[test]
+    fn viewports_score_only_their_cells() {
+        let target = target();
+        let viewport = Viewport::new(0, 0, 1, 4);
+        let across = vec![Placement::new(4, 0, 0, 3)];
+        assert_eq!(score(&across, &target), 0);
+        assert_eq!(score_in(&across, &target, &viewport), -2);
+
+        let mut bot = Bot::new(20, 10, 1);
+        let plan = bot.best_plan_in(2, 4, &target, &viewport).unwrap();
+        let total: i32 = plan.steps.iter().map(|step| score_in(&vec![step.placement], &target, &viewport) as i32).sum();
+        assert_eq!(plan.evaluation, total);
+        assert!(bot.history().next().is_none());
+    }
+}
@@ -1,20 +1,28 @@
 #![allow(dead_code)]
 
+use crate::board::Line;
 use crate::game::*;
+use crate::garbage::{attack, GARBAGE_CAP};
 use crate::piece::{Placement, Point};
 use enum_dispatch::enum_dispatch;
 use std::collections::{HashSet, VecDeque};
 
 #[enum_dispatch]
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
     PieceRotate,
     PieceMove,
+    DasShift,
     SoftDrop,
+    PartialSoftDrop,
+    SonicDrop,
     SetPiece,
     NextPiece,
     Hold,
     ClearLines,
+    AddGarbage,
+    SettleGarbage,
     Batch,
     HardDrop,
     PlacementActions,
@@ -27,6 +35,7 @@ pub trait Executable {
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PieceMove {
     moved: bool,
     dy: i8,
@@ -41,6 +50,10 @@ impl PieceMove {
             dx,
         }
     }
+
+    pub fn offset(&self) -> (i8, i8) {
+        (self.dy, self.dx)
+    }
 }
 
 impl Executable for PieceMove {
@@ -60,10 +73,56 @@ impl Executable for PieceMove {
     }
 }
 
+/// Moves the piece by `(dy, dx)` until it hits something or has moved `limit`
+/// times, returning how far it went.
+fn shift_until(game: &mut Game, dy: i8, dx: i8, limit: i8) -> i8 {
+    let mut step = PieceMove::new(dy, dx);
+    let mut distance = 0;
+    while distance < limit && step.execute(game) {
+        distance += 1;
+    }
+    distance
+}
+
+/// Holding left or right past DAS with instant ARR: slides to the wall.
+#[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DasShift {
+    dx: i8,
+    distance: i8,
+}
+
+impl DasShift {
+    pub fn new(dx: i8) -> Self {
+        Self { dx, distance: 0 }
+    }
+
+    pub fn dx(&self) -> i8 {
+        self.dx
+    }
+
+    pub fn distance(&self) -> i8 {
+        self.distance
+    }
+}
+
+impl Executable for DasShift {
+    fn execute(&mut self, game: &mut Game) -> bool {
+        self.distance = shift_until(game, 0, self.dx, i8::MAX);
+        self.distance > 0
+    }
+
+    fn undo(&mut self, game: &mut Game) {
+        game.active.shift(0, -self.dx * self.distance);
+    }
+}
+
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PieceRotate {
     direction: usize,
     before: Placement,
+    kick: usize,
 }
 
 impl PieceRotate {
@@ -71,8 +130,18 @@ impl PieceRotate {
         Self {
             direction,
             before: Placement::default(),
+            kick: 0,
         }
     }
+
+    pub fn direction(&self) -> usize {
+        self.direction
+    }
+
+    /// Which offset test the rotation passed, 0 being the plain rotation.
+    pub fn kick(&self) -> usize {
+        self.kick
+    }
 }
 
 impl Executable for PieceRotate {
@@ -80,10 +149,11 @@ impl Executable for PieceRotate {
         self.before = game.active;
         game.active.rotate(self.direction);
 
-        for [y, x] in game.active.get_offsets(self.direction) {
+        for (kick, [y, x]) in game.active.get_offsets(self.direction).into_iter().enumerate() {
             let mut command = PieceMove::new(-y, -x);
             // println!("{} {}", x, y);
             if command.execute(game) {
+                self.kick = kick;
                 return true;
             }
         }
@@ -98,6 +168,7 @@ impl Executable for PieceRotate {
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SoftDrop {
     distance: i8,
 }
@@ -106,10 +177,15 @@ impl SoftDrop {
     pub fn new() -> Self {
         Self { distance: 0 }
     }
+
+    pub fn distance(&self) -> i8 {
+        self.distance
+    }
 }
 
 impl Executable for SoftDrop {
     fn execute(&mut self, game: &mut Game) -> bool {
+        self.distance = 0;
         let mut down = PieceMove::new(-1, 0);
         while down.execute(game) {
             self.distance += 1;
@@ -122,7 +198,72 @@ impl Executable for SoftDrop {
     }
 }
 
+/// Soft drop released after at most `rows` rows.
+#[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartialSoftDrop {
+    rows: i8,
+    distance: i8,
+}
+
+impl PartialSoftDrop {
+    pub fn new(rows: i8) -> Self {
+        Self { rows, distance: 0 }
+    }
+
+    pub fn rows(&self) -> i8 {
+        self.rows
+    }
+
+    pub fn distance(&self) -> i8 {
+        self.distance
+    }
+}
+
+impl Executable for PartialSoftDrop {
+    fn execute(&mut self, game: &mut Game) -> bool {
+        self.distance = shift_until(game, -1, 0, self.rows);
+        self.distance > 0
+    }
+
+    fn undo(&mut self, game: &mut Game) {
+        game.active.shift(self.distance, 0);
+    }
+}
+
+/// Drops to the floor in a single input (a 20G soft drop). On the board
+/// this matches `SoftDrop`; they differ in time, where `SoftDrop` stands for
+/// holding the key until the piece gets there and a sonic drop only exists
+/// when soft drop is instant.
 #[derive(Default, Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SonicDrop {
+    distance: i8,
+}
+
+impl SonicDrop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn distance(&self) -> i8 {
+        self.distance
+    }
+}
+
+impl Executable for SonicDrop {
+    fn execute(&mut self, game: &mut Game) -> bool {
+        self.distance = shift_until(game, -1, 0, i8::MAX);
+        true
+    }
+
+    fn undo(&mut self, game: &mut Game) {
+        game.active.shift(self.distance, 0);
+    }
+}
+
+#[derive(Default, Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SetPiece {
     locations: [Point; 4],
     row: i8,
@@ -143,8 +284,11 @@ impl Executable for SetPiece {
             game.active.col,
         );
         for [r, c] in self.locations {
-            game.board
-                .add((r + self.row) as usize, (c + self.col) as usize);
+            game.board.paint(
+                (r + self.row) as usize,
+                (c + self.col) as usize,
+                game.active.piece_type,
+            );
         }
         true
     }
@@ -158,6 +302,7 @@ impl Executable for SetPiece {
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NextPiece {
     cur_piece: Placement,
     next_piece: usize,
@@ -186,9 +331,15 @@ impl NextPiece {
             next_piece: 8,
         }
     }
+
+    /// The piece that was active when this ran, i.e. the one just locked.
+    pub fn placed(&self) -> Placement {
+        self.cur_piece
+    }
 }
 
 #[derive(Default, Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Hold {
     first: bool,
     before: usize,
@@ -221,26 +372,32 @@ impl Executable for Hold {
             game.hold = None;
             game.queue.push(self.after);
         } else {
-            game.hold = Some(self.before);
+            game.hold = Some(self.after);
         }
         game.active = game.new_piece(self.before);
     }
 }
 
 #[derive(Default, Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClearLines {
-    line_indices: Vec<(usize, Vec<bool>)>,
+    line_indices: Vec<(usize, Line)>,
 }
 
 impl ClearLines {
     pub fn new() -> Self {
         Self::default()
     }
+
+    pub fn count(&self) -> usize {
+        self.line_indices.len()
+    }
 }
 
 impl Executable for ClearLines {
     fn execute(&mut self, game: &mut Game) -> bool {
-        for row in 0..game.board.height {
+        // top down, so clearing a row never shifts one we have yet to check
+        for row in (0..game.board.height).rev() {
             if let Some(line) = game.board.line_clear(row) {
                 self.line_indices.push((row, line));
             }
@@ -249,14 +406,130 @@ impl Executable for ClearLines {
     }
 
     fn undo(&mut self, game: &mut Game) {
-        self.line_indices.reverse();
-        while let Some((index, vec)) = self.line_indices.pop() {
-            game.board.arr.insert(index, vec);
+        while let Some((index, line)) = self.line_indices.pop() {
+            game.board.restore_line(index, line);
+        }
+    }
+}
+
+/// Pushes garbage rows in from the bottom, one per hole column, lifting the
+/// active piece out of the way if it can.
+#[derive(Default, Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AddGarbage {
+    holes: Vec<usize>,
+    pushed_off: Vec<Line>,
+    lift: i8,
+}
+
+impl AddGarbage {
+    pub fn new(holes: Vec<usize>) -> Self {
+        Self {
+            holes,
+            ..Self::default()
+        }
+    }
+
+    pub fn holes(&self) -> &[usize] {
+        &self.holes
+    }
+}
+
+impl Executable for AddGarbage {
+    fn execute(&mut self, game: &mut Game) -> bool {
+        let width = game.board.width;
+        for &hole in self.holes.iter() {
+            let cells = (0..width).map(|col| col != hole).collect();
+            let line = game.board.insert_bottom((cells, vec![None; width]));
+            self.pushed_off.push(line);
+        }
+
+        self.lift = 0;
+        let rows = self.holes.len() as i8;
+        while self.lift < rows && !game.board.piece_valid_location(&game.active) {
+            game.active.shift(1, 0);
+            self.lift += 1;
+        }
+        if !game.board.piece_valid_location(&game.active) {
+            // topped out either way, leave it where it was
+            game.active.shift(-self.lift, 0);
+            self.lift = 0;
+        }
+        true
+    }
+
+    fn undo(&mut self, game: &mut Game) {
+        game.active.shift(-self.lift, 0);
+        while let Some(line) = self.pushed_off.pop() {
+            game.board.remove_bottom(line);
+        }
+    }
+}
+
+/// Runs after a piece locks: attack from the clear cancels pending garbage
+/// and whatever is left over is sent. A piece that clears nothing lets up
+/// to `GARBAGE_CAP` pending rows in.
+#[derive(Default, Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SettleGarbage {
+    lines_cleared: usize,
+    cancelled: Vec<usize>,
+    sent: usize,
+    added: Option<AddGarbage>,
+}
+
+impl SettleGarbage {
+    pub fn new(lines_cleared: usize) -> Self {
+        Self {
+            lines_cleared,
+            ..Self::default()
+        }
+    }
+
+    pub fn sent(&self) -> usize {
+        self.sent
+    }
+
+    pub fn cancelled(&self) -> usize {
+        self.cancelled.len()
+    }
+}
+
+impl Executable for SettleGarbage {
+    fn execute(&mut self, game: &mut Game) -> bool {
+        let perfect_clear = self.lines_cleared > 0 && game.board.is_empty();
+        let attack = attack(self.lines_cleared, perfect_clear);
+        let cancel = attack.min(game.pending_garbage.len());
+        self.cancelled = game.pending_garbage.drain(..cancel).collect();
+        self.sent = attack - cancel;
+        game.attack_sent += self.sent;
+
+        self.added = None;
+        if self.lines_cleared == 0 && !game.pending_garbage.is_empty() {
+            let rows = game.pending_garbage.len().min(GARBAGE_CAP);
+            let mut add = AddGarbage::new(game.pending_garbage.drain(..rows).collect());
+            add.execute(game);
+            self.added = Some(add);
+        }
+        true
+    }
+
+    fn undo(&mut self, game: &mut Game) {
+        if let Some(mut add) = self.added.take() {
+            add.undo(game);
+            for &hole in add.holes().iter().rev() {
+                game.pending_garbage.push_front(hole);
+            }
+        }
+        game.attack_sent -= self.sent;
+        for &hole in self.cancelled.iter().rev() {
+            game.pending_garbage.push_front(hole);
         }
     }
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HardDrop {
     batch: Batch,
 }
@@ -266,18 +539,51 @@ impl HardDrop {
         let sd = SoftDrop::new().into();
         let set = SetPiece::new().into();
         let clr = ClearLines::new().into();
+        let settle = SettleGarbage::new(0).into();
         let nxt = NextPiece::new().into();
 
+        // garbage enters once the next piece is out, so only that one can
+        // be pushed up by it
         let batch = Batch {
-            commands: VecDeque::from([sd, set, clr, nxt]),
+            commands: VecDeque::from([sd, set, clr, nxt, settle]),
         };
         Self { batch }
     }
+
+    pub fn lines_cleared(&self) -> usize {
+        self.batch
+            .commands
+            .iter()
+            .map(|command| match command {
+                Command::ClearLines(clear) => clear.count(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Where the piece locked, once this has been executed.
+    pub fn placement(&self) -> Option<Placement> {
+        self.batch.commands.iter().find_map(|command| match command {
+            Command::NextPiece(next) => Some(next.placed()),
+            _ => None,
+        })
+    }
 }
 
 impl Executable for HardDrop {
     fn execute(&mut self, game: &mut Game) -> bool {
-        self.batch.execute(game)
+        // garbage settles on how many lines the clear before it took
+        let mut lines_cleared = 0;
+        for command in self.batch.commands.iter_mut() {
+            if let Command::SettleGarbage(settle) = command {
+                *settle = SettleGarbage::new(lines_cleared);
+            }
+            command.execute(game);
+            if let Command::ClearLines(clear) = command {
+                lines_cleared = clear.count();
+            }
+        }
+        true
     }
 
     fn undo(&mut self, game: &mut Game) {
@@ -286,6 +592,7 @@ impl Executable for HardDrop {
 }
 
 #[derive(Default, Clone, Hash, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Batch {
     pub commands: VecDeque<Command>,
 }
@@ -314,6 +621,7 @@ impl Executable for Batch {
 }
 
 #[derive(Clone, Hash, Eq, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PlacementActions {
     pub batch: Batch,
     pub placement: Vec<Placement>,
@@ -384,4 +692,247 @@ pub fn duplicate_placement(used: &HashSet<PlacementActions>, piece: &Placement)
              placement,
          }| placement.contains(piece),
     )
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Everything the commands touch, for comparing before and after. The
+    /// queue may have drawn a bag ahead, so only what it deals next counts.
+    fn snapshot(game: &Game) -> (crate::board::Board, Placement, Option<usize>, Vec<usize>) {
+        let mut queue = game.queue.clone();
+        (
+            game.board.clone(),
+            game.active,
+            game.hold,
+            (0..14).map(|_| queue.next()).collect(),
+        )
+    }
+
+    fn round_trip(game: &mut Game, mut command: Command) {
+        let before = snapshot(game);
+        command.execute(game);
+        command.undo(game);
+        assert_eq!(snapshot(game), before);
+    }
+
+    #[test]
+    fn hold_undo_restores_the_held_piece() {
+        let mut game = Game::new(20, 10, 1);
+        let first = game.active.piece_type;
+        round_trip(&mut game, Hold::new().into());
+
+        Hold::new().execute(&mut game);
+        assert_eq!(game.hold, Some(first));
+        let second = game.active.piece_type;
+        let mut swap = Hold::new();
+        swap.execute(&mut game);
+        assert_eq!((game.active.piece_type, game.hold), (first, Some(second)));
+        swap.undo(&mut game);
+        assert_eq!((game.active.piece_type, game.hold), (second, Some(first)));
+    }
+
+    #[test]
+    fn clear_lines_round_trip() {
+        let mut game = Game::new(20, 10, 1);
+        for row in [0, 1, 3] {
+            for col in 0..10 {
+                game.board.add(row, col);
+            }
+        }
+        game.board.add(2, 4);
+        game.board.add(4, 7);
+        let before = snapshot(&game);
+
+        let mut clear = ClearLines::new();
+        clear.execute(&mut game);
+        assert_eq!(clear.count(), 3);
+        assert_eq!(game.board.arr.len(), 20);
+        assert!(game.board.get(0, 4) && game.board.get(1, 7));
+        assert_eq!(game.board.arr.iter().flatten().filter(|&&cell| cell).count(), 2);
+
+        clear.undo(&mut game);
+        assert_eq!(snapshot(&game), before);
+    }
+
+    #[test]
+    fn soft_drop_distance_resets() {
+        let mut game = Game::new(20, 10, 1);
+        let mut drop = SoftDrop::new();
+        drop.execute(&mut game);
+        let distance = drop.distance();
+        drop.undo(&mut game);
+        drop.execute(&mut game);
+        assert_eq!(drop.distance(), distance);
+        round_trip(&mut game, drop.into());
+    }
+
+    #[test]
+    fn das_shift_slides_to_the_wall() {
+        let mut game = Game::new(20, 10, 1);
+        let spawn = game.active;
+        let mut shift = DasShift::new(-1);
+        assert!(shift.execute(&mut game));
+        assert!(shift.distance() > 1);
+        assert!(!PieceMove::new(0, -1).execute(&mut game));
+        shift.undo(&mut game);
+        assert_eq!(game.active, spawn);
+
+        // already against the wall, so it goes nowhere and undoes nothing
+        DasShift::new(1).execute(&mut game);
+        let wall = game.active;
+        let mut again = DasShift::new(1);
+        assert!(!again.execute(&mut game));
+        again.undo(&mut game);
+        assert_eq!(game.active, wall);
+    }
+
+    #[test]
+    fn partial_soft_drop_stops_early() {
+        let mut game = Game::new(20, 10, 1);
+        let spawn = game.active;
+        let mut drop = PartialSoftDrop::new(3);
+        assert!(drop.execute(&mut game));
+        assert_eq!(drop.distance(), 3);
+        assert_eq!(game.active.row, spawn.row - 3);
+        drop.undo(&mut game);
+        assert_eq!(game.active, spawn);
+
+        // asked for more than there is room for, it stops on the floor
+        let mut drop = PartialSoftDrop::new(100);
+        drop.execute(&mut game);
+        assert!(game.board.piece_grounded(&game.active));
+        drop.undo(&mut game);
+        assert_eq!(game.active, spawn);
+        round_trip(&mut game, PartialSoftDrop::new(5).into());
+    }
+
+    #[test]
+    fn sonic_drop_lands_where_soft_drop_does() {
+        let mut game = Game::new(20, 10, 1);
+        let spawn = game.active;
+        let mut soft = SoftDrop::new();
+        soft.execute(&mut game);
+        let floor = game.active;
+        soft.undo(&mut game);
+
+        let mut sonic = SonicDrop::new();
+        assert!(sonic.execute(&mut game));
+        assert_eq!(game.active, floor);
+        assert_eq!(sonic.distance(), soft.distance());
+        sonic.undo(&mut game);
+        assert_eq!(game.active, spawn);
+        round_trip(&mut game, SonicDrop::new().into());
+    }
+
+    #[test]
+    fn hard_drop_round_trip() {
+        let mut game = Game::new(20, 10, 1);
+        round_trip(&mut game, HardDrop::new().into());
+    }
+
+    #[test]
+    fn add_garbage_round_trip() {
+        let mut game = Game::new(20, 10, 1);
+        game.board.paint(0, 0, 6);
+        let before = snapshot(&game);
+        let mut add = AddGarbage::new(vec![2, 5]);
+        add.execute(&mut game);
+        assert!(!game.board.get(0, 5) && !game.board.get(1, 2));
+        assert_eq!(game.board.kind(2, 0), Some(6));
+        add.undo(&mut game);
+        assert_eq!(snapshot(&game), before);
+    }
+
+    #[test]
+    fn add_garbage_lifts_a_buried_piece() {
+        let mut game = Game::new(20, 10, 1);
+        game.active = game.board.ghost(&game.active);
+        let landed = game.active;
+        let mut add = AddGarbage::new(vec![0]);
+        add.execute(&mut game);
+        assert_eq!(game.active.row, landed.row + 1);
+        add.undo(&mut game);
+        assert_eq!(game.active, landed);
+    }
+
+    #[test]
+    fn garbage_enters_without_a_clear() {
+        let mut game = Game::new(20, 10, 1);
+        game.pending_garbage.extend([4, 4]);
+        let mut settle = SettleGarbage::new(0);
+        settle.execute(&mut game);
+        assert!(game.pending_garbage.is_empty());
+        assert!(!game.board.get(0, 4) && game.board.get(1, 3));
+        settle.undo(&mut game);
+        assert!(game.board.is_empty());
+        assert_eq!(game.pending_garbage, [4, 4]);
+    }
+
+    #[test]
+    fn clears_cancel_before_they_send() {
+        let mut game = Game::new(20, 10, 1);
+        // not a perfect clear, which would send ten more
+        game.board.add(0, 0);
+        game.pending_garbage.extend([1, 2, 3]);
+
+        // a double is one line of attack, all of it cancelled
+        let mut double = SettleGarbage::new(2);
+        double.execute(&mut game);
+        assert_eq!((double.cancelled(), double.sent()), (1, 0));
+        assert_eq!(game.pending_garbage, [2, 3]);
+
+        // a tetris is four, two cancel and two go out
+        let mut tetris = SettleGarbage::new(4);
+        tetris.execute(&mut game);
+        assert_eq!((tetris.cancelled(), tetris.sent()), (2, 2));
+        assert_eq!((game.pending_garbage.len(), game.attack_sent), (0, 2));
+
+        tetris.undo(&mut game);
+        double.undo(&mut game);
+        assert_eq!((game.pending_garbage, game.attack_sent), ([1, 2, 3].into(), 0));
+    }
+
+    #[test]
+    fn hard_drop_locks_before_garbage_rises() {
+        let mut game = Game::new(20, 10, 1);
+        // a T upside down, its left wing resting on (3, 3) and a cell under
+        // where it would be after one row of lift
+        game.board.bulk_add(vec![(0, 4), (3, 3)]);
+        game.active = Placement::new(6, 2, 4, 4);
+        let landed = game.active;
+        assert_eq!(game.board.ghost(&landed), landed);
+        game.pending_garbage.extend([0, 0, 0]);
+        let before = snapshot(&game);
+
+        let mut drop = HardDrop::new();
+        drop.execute(&mut game);
+        assert_eq!(drop.placement(), Some(landed));
+        // the locked piece went up with the board, the next one spawned as usual
+        for [row, col] in landed.abs_locations() {
+            assert!(game.board.get(row as usize + 3, col as usize));
+        }
+        assert_eq!(game.active, game.new_piece(game.active.piece_type));
+
+        drop.undo(&mut game);
+        assert_eq!(snapshot(&game), before);
+        assert_eq!(game.pending_garbage, [0, 0, 0]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn executed_commands_undo_after_serde() {
+        let mut game = Game::new(20, 10, 1);
+        game.board.paint(0, 0, 6);
+        game.pending_garbage.push_back(3);
+        let before = snapshot(&game);
+        let mut drop: Command = HardDrop::new().into();
+        drop.execute(&mut game);
+
+        let json = serde_json::to_string(&(&game, &drop)).unwrap();
+        let (mut game, mut drop): (Game, Command) = serde_json::from_str(&json).unwrap();
+        drop.undo(&mut game);
+        assert_eq!(snapshot(&game), before);
+        assert_eq!(game.pending_garbage, [3]);
+    }
+}
@@ -0,0 +1,185 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::render::{fill_cell, EMPTY};
+use image::{Rgba, RgbaImage};
+use std::fmt::{Display, Formatter};
+
+const CORRECT: Rgba<u8> = Rgba([70, 200, 90, 255]);
+const MISSING: Rgba<u8> = Rgba([240, 200, 50, 255]);
+const WRONG: Rgba<u8> = Rgba([225, 50, 55, 255]);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CellDiff {
+    Correct,
+    Missing,
+    Wrong,
+    Empty,
+}
+
+pub fn cell_diff(target: &Board, painted: &Board, row: usize, col: usize) -> CellDiff {
+    let filled = row < painted.height && col < painted.width && painted.get(row, col);
+    match (target.get(row, col), filled) {
+        (true, true) => CellDiff::Correct,
+        (true, false) => CellDiff::Missing,
+        (false, true) => CellDiff::Wrong,
+        (false, false) => CellDiff::Empty,
+    }
+}
+
+/// How well `painted` reproduces `target`, counted over the target's cells.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Comparison {
+    pub correct: usize,
+    pub missing: usize,
+    pub wrong: usize,
+}
+
+fn ratio(num: usize, den: usize) -> f32 {
+    if den == 0 {
+        1.0
+    } else {
+        num as f32 / den as f32
+    }
+}
+
+impl Comparison {
+    pub fn precision(&self) -> f32 {
+        ratio(self.correct, self.correct + self.wrong)
+    }
+
+    pub fn recall(&self) -> f32 {
+        ratio(self.correct, self.correct + self.missing)
+    }
+
+    pub fn iou(&self) -> f32 {
+        ratio(self.correct, self.correct + self.missing + self.wrong)
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "precision {:.3} recall {:.3} IoU {:.3} ({} correct, {} missing, {} wrong)",
+            self.precision(),
+            self.recall(),
+            self.iou(),
+            self.correct,
+            self.missing,
+            self.wrong
+        )
+    }
+}
+
+pub fn compare(target: &Board, painted: &Board) -> Comparison {
+    let mut out = Comparison::default();
+    for row in 0..target.height {
+        for col in 0..target.width {
+            match cell_diff(target, painted, row, col) {
+                CellDiff::Correct => out.correct += 1,
+                CellDiff::Missing => out.missing += 1,
+                CellDiff::Wrong => out.wrong += 1,
+                CellDiff::Empty => {}
+            }
+        }
+    }
+    out
+}
+
+/// The target with the painted board laid over it, coloured with ANSI escapes:
+/// green where both agree, yellow where paint is missing, red where it is wrong.
+pub fn diff_string(target: &Board, painted: &Board) -> String {
+    let mut out = String::new();
+    for row in (0..target.height).rev() {
+        for col in 0..target.width {
+            out.push_str(match cell_diff(target, painted, row, col) {
+                CellDiff::Correct => "\x1b[32m■\x1b[0m ",
+                CellDiff::Missing => "\x1b[33m□\x1b[0m ",
+                CellDiff::Wrong => "\x1b[31m■\x1b[0m ",
+                CellDiff::Empty => "□ ",
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn render_diff(target: &Board, painted: &Board, cell: u32) -> RgbaImage {
+    let mut img = RgbaImage::from_pixel(target.width as u32 * cell, target.height as u32 * cell, EMPTY);
+    for row in 0..target.height {
+        for col in 0..target.width {
+            let colour = match cell_diff(target, painted, row, col) {
+                CellDiff::Correct => CORRECT,
+                CellDiff::Missing => MISSING,
+                CellDiff::Wrong => WRONG,
+                CellDiff::Empty => continue,
+            };
+            fill_cell(&mut img, target.height, row, col, cell, colour);
+        }
+    }
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A target with cells (0, 0), (0, 1) and (1, 0), painted with (0, 0),
+    /// (0, 1) and (1, 1).
+    fn boards() -> (Board, Board) {
+        let mut target = Board::new(3, 2);
+        target.bulk_add(vec![(0, 0), (0, 1), (1, 0)]);
+        let mut painted = Board::new(3, 2);
+        painted.bulk_add(vec![(0, 0), (0, 1), (1, 1)]);
+        (target, painted)
+    }
+
+    #[test]
+    fn comparison_counts_each_kind_of_cell() {
+        let (target, painted) = boards();
+        let comparison = compare(&target, &painted);
+        assert_eq!(
+            comparison,
+            Comparison {
+                correct: 2,
+                missing: 1,
+                wrong: 1,
+            }
+        );
+        assert_eq!(comparison.precision(), 2.0 / 3.0);
+        assert_eq!(comparison.recall(), 2.0 / 3.0);
+        assert_eq!(comparison.iou(), 0.5);
+    }
+
+    #[test]
+    fn nothing_to_paint_is_perfect() {
+        let empty = Board::new(3, 2);
+        let comparison = compare(&empty, &empty);
+        assert_eq!(comparison, Comparison::default());
+        assert_eq!((comparison.precision(), comparison.recall(), comparison.iou()), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn cells_off_the_painted_board_are_missing() {
+        let (target, _) = boards();
+        let small = Board::new(1, 1);
+        assert_eq!(cell_diff(&target, &small, 0, 1), CellDiff::Missing);
+        assert_eq!(cell_diff(&target, &small, 0, 2), CellDiff::Empty);
+    }
+
+    #[test]
+    fn diff_views() {
+        let (target, painted) = boards();
+        let text = diff_string(&target, &painted);
+        let rows: Vec<&str> = text.lines().collect();
+        assert_eq!(rows[0], "\x1b[33m□\x1b[0m \x1b[31m■\x1b[0m □ ");
+        assert_eq!(rows[1], "\x1b[32m■\x1b[0m \x1b[32m■\x1b[0m □ ");
+
+        let img = render_diff(&target, &painted, 1);
+        assert_eq!(*img.get_pixel(0, 1), CORRECT);
+        assert_eq!(*img.get_pixel(0, 0), MISSING);
+        assert_eq!(*img.get_pixel(1, 0), WRONG);
+        assert_eq!(*img.get_pixel(2, 0), EMPTY);
+    }
+}
@@ -0,0 +1,244 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::control::*;
+use crate::game::new_piece;
+use crate::piece::Placement;
+use crate::plan::Plan;
+use crate::reachability::reachable_from;
+
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Key {
+    Left,
+    Right,
+    DasLeft,
+    DasRight,
+    Cw,
+    Ccw,
+    Flip,
+    SoftDrop,
+    SoftDropBy(i8),
+    SonicDrop,
+    HardDrop,
+    Hold,
+}
+
+impl Key {
+    pub fn command(self) -> Command {
+        match self {
+            Key::Left => PieceMove::new(0, -1).into(),
+            Key::Right => PieceMove::new(0, 1).into(),
+            Key::DasLeft => DasShift::new(-1).into(),
+            Key::DasRight => DasShift::new(1).into(),
+            Key::Cw => PieceRotate::new(1).into(),
+            Key::Flip => PieceRotate::new(2).into(),
+            Key::Ccw => PieceRotate::new(3).into(),
+            Key::SoftDrop => SoftDrop::new().into(),
+            Key::SoftDropBy(rows) => PartialSoftDrop::new(rows).into(),
+            Key::SonicDrop => SonicDrop::new().into(),
+            Key::HardDrop => HardDrop::new().into(),
+            Key::Hold => Hold::new().into(),
+        }
+    }
+
+    /// The keys a player would press to perform `command`, or `None` if it
+    /// contains engine-only steps such as `SetPiece`.
+    pub fn from_command(command: &Command) -> Option<Vec<Key>> {
+        let key = match command {
+            Command::PieceMove(step) => match step.offset() {
+                (0, -1) => Key::Left,
+                (0, 1) => Key::Right,
+                _ => return None,
+            },
+            Command::DasShift(shift) if shift.dx() < 0 => Key::DasLeft,
+            Command::DasShift(_) => Key::DasRight,
+            Command::PieceRotate(rotate) => match rotate.direction() {
+                1 => Key::Cw,
+                2 => Key::Flip,
+                _ => Key::Ccw,
+            },
+            Command::SoftDrop(_) => Key::SoftDrop,
+            Command::PartialSoftDrop(drop) => Key::SoftDropBy(drop.rows()),
+            Command::SonicDrop(_) => Key::SonicDrop,
+            Command::HardDrop(_) => Key::HardDrop,
+            Command::Hold(_) => Key::Hold,
+            Command::Batch(batch) => return Key::from_commands(batch.commands.iter()),
+            Command::PlacementActions(actions) => {
+                return Key::from_commands(actions.batch.commands.iter())
+            }
+            Command::SetPiece(_)
+            | Command::NextPiece(_)
+            | Command::ClearLines(_)
+            | Command::AddGarbage(_)
+            | Command::SettleGarbage(_) => return None,
+        };
+        Some(vec![key])
+    }
+
+    pub fn from_commands<'a>(commands: impl Iterator<Item = &'a Command>) -> Option<Vec<Key>> {
+        let mut out = Vec::new();
+        for command in commands {
+            out.extend(Key::from_command(command)?);
+        }
+        Some(out)
+    }
+}
+
+/// Which inputs count as a single key press when looking for the shortest
+/// sequence. Without `soft_drop`, pieces can only be hard dropped from above.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputModel {
+    pub das: bool,
+    pub allow_180: bool,
+    pub soft_drop: bool,
+}
+
+impl Default for InputModel {
+    fn default() -> Self {
+        Self {
+            das: true,
+            allow_180: true,
+            soft_drop: true,
+        }
+    }
+}
+
+impl InputModel {
+    pub fn keys(&self) -> Vec<Key> {
+        let mut keys = vec![Key::Left, Key::Right, Key::Cw, Key::Ccw];
+        if self.das {
+            keys.extend([Key::DasLeft, Key::DasRight]);
+        }
+        if self.allow_180 {
+            keys.push(Key::Flip);
+        }
+        if self.soft_drop {
+            keys.push(Key::SoftDrop);
+        }
+        keys
+    }
+}
+
+/// Shortest key sequence for one placement, ending in `Key::HardDrop`.
+/// `extra` is set when the board forces more keys than the same placement
+/// would need on an empty board.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Finesse {
+    pub keys: Vec<Key>,
+    pub extra: bool,
+}
+
+impl Finesse {
+    pub fn commands(&self) -> Vec<Command> {
+        self.keys.iter().map(|key| key.command()).collect()
+    }
+}
+
+fn shortest(board: &Board, spawn: Placement, target: &Placement, model: &InputModel) -> Option<Vec<Key>> {
+    reachable_from(board, spawn, target, model).ok().map(|path| path.keys())
+}
+
+pub fn finesse(board: &Board, spawn: Placement, target: &Placement, model: &InputModel) -> Option<Finesse> {
+    let keys = shortest(board, spawn, target, model)?;
+    // the same column and rotation on an empty board, as in a finesse chart
+    let empty = Board::new(board.width, board.height);
+    let ideal = shortest(&empty, spawn, &empty.ghost(target), model);
+    let extra = ideal.is_none_or(|ideal| keys.len() > ideal.len());
+    Some(Finesse { keys, extra })
+}
+
+/// Replaces the search inputs of every step in `plan` with its finesse-optimal
+/// keys, starting from `board`. Steps that cannot be expressed under `model`
+/// keep their original inputs and get `None`.
+pub fn refine(plan: &mut Plan, board: &Board, model: &InputModel) -> Vec<Option<Finesse>> {
+    let mut before = board.clone();
+    let mut out = Vec::new();
+    for step in plan.steps.iter_mut() {
+        let spawn = new_piece(step.placement.piece_type, before.height, before.width);
+        let found = finesse(&before, spawn, &step.placement, model);
+        if let Some(found) = &found {
+            step.inputs = found.commands();
+            if step.hold {
+                step.inputs.insert(0, Hold::new().into());
+            }
+        }
+        out.push(found);
+        before = step.board.clone();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const O: usize = 2;
+
+    fn landing(board: &Board, piece: usize, rotation: usize, dx: i8) -> (Placement, Placement) {
+        let spawn = new_piece(piece, board.height, board.width);
+        let mut target = spawn;
+        target.rotate(rotation);
+        target.shift(0, dx);
+        (spawn, board.ghost(&target))
+    }
+
+    #[test]
+    fn o_piece_chart() {
+        let board = Board::new(10, 20);
+        let model = InputModel::default();
+        let keys = |dx| {
+            let (spawn, target) = landing(&board, O, 0, dx);
+            finesse(&board, spawn, &target, &model).unwrap().keys
+        };
+        assert_eq!(keys(0), vec![Key::HardDrop]);
+        assert_eq!(keys(-1), vec![Key::Left, Key::HardDrop]);
+        assert_eq!(keys(-4), vec![Key::DasLeft, Key::HardDrop]);
+        assert_eq!(keys(4), vec![Key::DasRight, Key::HardDrop]);
+        assert_eq!(keys(-3).len(), 3);
+    }
+
+    #[test]
+    fn empty_board_needs_at_most_three_keys() {
+        let board = Board::new(10, 20);
+        let model = InputModel::default();
+        for piece in 0..7 {
+            for rotation in 0..4 {
+                for dx in -5..=5 {
+                    let (spawn, target) = landing(&board, piece, rotation, dx);
+                    if !board.piece_valid_location(&target) {
+                        continue;
+                    }
+                    let found = finesse(&board, spawn, &target, &model).unwrap();
+                    let (last, moves) = found.keys.split_last().unwrap();
+                    assert_eq!(*last, Key::HardDrop);
+                    assert!(moves.len() <= 3, "{:?} {:?}", target, found.keys);
+                    assert!(!found.extra);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn overhang_costs_extra_keys() {
+        let mut board = Board::new(10, 20);
+        board.bulk_add(vec![(2, 0), (2, 1)]);
+        let spawn = new_piece(O, board.height, board.width);
+        // on the floor in the two leftmost columns, under the overhang
+        let mut target = spawn;
+        let bottom = spawn.abs_locations().iter().map(|[row, _]| *row).min().unwrap();
+        target.shift(-bottom, -4);
+        assert!(board.piece_valid_placement(&target));
+
+        let found = finesse(&board, spawn, &target, &InputModel::default()).unwrap();
+        assert!(found.extra);
+        assert!(found.keys.contains(&Key::SoftDrop));
+        let no_soft_drop = InputModel {
+            soft_drop: false,
+            ..InputModel::default()
+        };
+        assert!(finesse(&board, spawn, &target, &no_soft_drop).is_none());
+    }
+}
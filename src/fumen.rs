@@ -0,0 +1,532 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::game::Game;
+use crate::piece::Placement;
+use crate::plan::Plan;
+
+const WIDTH: usize = 10;
+// visible rows, plus one hidden garbage row underneath
+const TOP: usize = 23;
+const BLOCKS: usize = (TOP + 1) * WIDTH;
+const GRAY: u8 = 8;
+const EMPTY_DIFF: usize = 8 * BLOCKS + BLOCKS - 1;
+
+const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE: &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const MAX_COMMENT: usize = 4095;
+
+// fumen numbers pieces I L O Z T J S from 1; ours are Z L O S I J T from 0
+const TO_FUMEN: [u8; 7] = [4, 2, 3, 7, 1, 6, 5];
+// fumen rotations are 180, right, spawn, left; ours are spawn, right, 180, left
+const ROTATION_TO_FUMEN: [usize; 4] = [2, 1, 0, 3];
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FumenError {
+    Version,
+    Corrupt,
+    Width(usize),
+    TooTall,
+}
+
+/// One page of a fumen: the field before `piece` is placed. With `lock`
+/// set, the next page starts from this field with the piece placed and
+/// full lines cleared.
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub board: Board,
+    pub piece: Option<Placement>,
+    pub comment: String,
+    pub lock: bool,
+}
+
+impl Page {
+    pub fn new(board: Board, piece: Option<Placement>) -> Self {
+        Self {
+            board,
+            piece,
+            comment: String::new(),
+            lock: true,
+        }
+    }
+}
+
+type Field = [u8; BLOCKS];
+
+fn index(row: usize, col: usize) -> usize {
+    (TOP - 1 - row) * WIDTH + col
+}
+
+fn to_field(board: &Board) -> Result<Field, FumenError> {
+    if board.width != WIDTH {
+        return Err(FumenError::Width(board.width));
+    }
+    let mut field = [0; BLOCKS];
+    for row in 0..board.height {
+        for col in 0..WIDTH {
+            if board.get(row, col) {
+                if row >= TOP {
+                    return Err(FumenError::TooTall);
+                }
+                field[index(row, col)] = board.kind(row, col).map_or(GRAY, |kind| TO_FUMEN[kind]);
+            }
+        }
+    }
+    Ok(field)
+}
+
+fn to_board(field: &Field, height: usize) -> Result<Board, FumenError> {
+    let mut board = Board::new(WIDTH, height);
+    for row in 0..TOP {
+        for col in 0..WIDTH {
+            let cell = field[index(row, col)];
+            if cell != 0 {
+                if row >= height {
+                    return Err(FumenError::TooTall);
+                }
+                match TO_FUMEN.iter().position(|&kind| kind == cell) {
+                    Some(kind) => board.paint(row, col, kind),
+                    None => board.add(row, col),
+                }
+            }
+        }
+    }
+    Ok(board)
+}
+
+// fumen stores O, I, S and Z by a different cell than their rotation centre
+fn position_shift(fumen_type: u8, rotation: usize) -> (i32, i32) {
+    match (fumen_type, rotation) {
+        (3, 3) => (-1, 1),
+        (3, 0) => (-1, 0),
+        (3, 2) => (0, 1),
+        (1, 0) => (-1, 0),
+        (1, 3) => (0, 1),
+        (7, 2) => (0, 1),
+        (7, 1) => (1, 0),
+        (4, 2) => (0, 1),
+        (4, 3) => (-1, 0),
+        _ => (0, 0),
+    }
+}
+
+fn encode_piece(piece: &Option<Placement>) -> Result<usize, FumenError> {
+    let Some(piece) = piece else {
+        return Ok(0);
+    };
+    let kind = TO_FUMEN[piece.piece_type];
+    let rotation = ROTATION_TO_FUMEN[piece.rotation_state];
+    let (dx, dy) = position_shift(kind, rotation);
+    let (x, y) = (piece.col as i32 + dx, piece.row as i32 + dy);
+    if !(0..WIDTH as i32).contains(&x) || !(0..TOP as i32).contains(&y) {
+        return Err(FumenError::TooTall);
+    }
+    let position = (TOP as i32 - y - 1) as usize * WIDTH + x as usize;
+    Ok(kind as usize + 8 * (rotation + 4 * position))
+}
+
+/// The piece at `position`, or `None` if it doesn't fit on the field; the
+/// last row of positions is the hidden garbage row, so nothing fits there.
+fn decode_piece(kind: u8, rotation: usize, position: usize) -> Option<Placement> {
+    let piece_type = TO_FUMEN.iter().position(|&k| k == kind)?;
+    let (dx, dy) = position_shift(kind, rotation);
+    let x = (position % WIDTH) as i32 - dx;
+    let y = TOP as i32 - (position / WIDTH) as i32 - 1 - dy;
+    let rotation_state = ROTATION_TO_FUMEN.iter().position(|&r| r == rotation)?;
+    let piece = Placement::new(piece_type, rotation_state, y as i8, x as i8);
+    let inside = |[row, col]: [i8; 2]| {
+        (0..TOP as i8).contains(&row) && (0..WIDTH as i8).contains(&col)
+    };
+    piece.abs_locations().into_iter().all(inside).then_some(piece)
+}
+
+fn lock(field: &mut Field, piece: &Option<Placement>) -> Result<(), FumenError> {
+    if let Some(piece) = piece {
+        for [row, col] in piece.abs_locations() {
+            if !(0..TOP as i8).contains(&row) || !(0..WIDTH as i8).contains(&col) {
+                return Err(FumenError::TooTall);
+            }
+            field[index(row as usize, col as usize)] = TO_FUMEN[piece.piece_type];
+        }
+    }
+
+    let mut rows: Vec<[u8; WIDTH]> = (0..TOP)
+        .map(|row| std::array::from_fn(|col| field[index(row, col)]))
+        .filter(|cells| cells.contains(&0))
+        .collect();
+    rows.resize(TOP, [0; WIDTH]);
+    for (row, cells) in rows.iter().enumerate() {
+        for (col, &cell) in cells.iter().enumerate() {
+            field[index(row, col)] = cell;
+        }
+    }
+    Ok(())
+}
+
+fn rise(field: &mut Field) {
+    for row in (0..TOP).rev() {
+        for col in 0..WIDTH {
+            field[index(row, col)] = if row == 0 {
+                field[TOP * WIDTH + col]
+            } else {
+                field[index(row - 1, col)]
+            };
+        }
+    }
+    field[TOP * WIDTH..].fill(0);
+}
+
+fn mirror(field: &mut Field) {
+    for row in 0..TOP {
+        field[index(row, 0)..index(row, 0) + WIDTH].reverse();
+    }
+}
+
+/// JavaScript's `escape`, stopping before the first character that would
+/// take it past `MAX_COMMENT`, so no escape (or surrogate pair) is cut in half.
+fn escape(comment: &str) -> String {
+    let mut out = String::new();
+    for ch in comment.chars() {
+        let escaped: String = if ch.is_ascii_alphanumeric() || "@*_+-./".contains(ch) {
+            ch.to_string()
+        } else {
+            ch.encode_utf16(&mut [0; 2])
+                .iter()
+                .map(|&c| match c {
+                    0..=255 => format!("%{:02X}", c),
+                    _ => format!("%u{:04X}", c),
+                })
+                .collect()
+        };
+        if out.len() + escaped.len() > MAX_COMMENT {
+            break;
+        }
+        out.push_str(&escaped);
+    }
+    out
+}
+
+fn unescape(comment: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = comment;
+    while let Some(ch) = rest.chars().next() {
+        let hex = |s: &str| u16::from_str_radix(s, 16).ok();
+        let escaped = match (rest.get(1..2), rest.get(2..6), rest.get(1..3)) {
+            (Some("u"), Some(long), _) if ch == '%' => hex(long).map(|c| (c, 6)),
+            (_, _, Some(short)) if ch == '%' => hex(short).map(|c| (c, 3)),
+            _ => None,
+        };
+        let (unit, len) = escaped.unwrap_or((ch as u16, 1));
+        units.push(unit);
+        rest = &rest[len..];
+    }
+    String::from_utf16_lossy(&units)
+}
+
+struct Writer {
+    values: Vec<usize>,
+}
+
+impl Writer {
+    fn push(&mut self, mut value: usize, digits: usize) {
+        for _ in 0..digits {
+            self.values.push(value % 64);
+            value /= 64;
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn poll(&mut self, digits: usize) -> Result<usize, FumenError> {
+        let mut value = 0;
+        for i in 0..digits {
+            let c = *self.data.get(i).ok_or(FumenError::Corrupt)?;
+            let digit = TABLE.iter().position(|&t| t == c).ok_or(FumenError::Corrupt)?;
+            value += digit * 64usize.pow(i as u32);
+        }
+        self.data = &self.data[digits..];
+        Ok(value)
+    }
+}
+
+pub fn encode(pages: &[Page]) -> Result<String, FumenError> {
+    let mut out = Writer { values: Vec::new() };
+    let mut prev = [0; BLOCKS];
+    let mut prev_comment = "";
+    let mut repeat: Option<usize> = None;
+
+    for (i, page) in pages.iter().enumerate() {
+        let mut field = to_field(&page.board)?;
+
+        // run length encode the difference from the last page
+        let mut runs = Writer { values: Vec::new() };
+        let diffs: Vec<usize> = (0..BLOCKS).map(|i| (field[i] + 8 - prev[i]) as usize).collect();
+        let mut start = 0;
+        for i in 1..=BLOCKS {
+            if i == BLOCKS || diffs[i] != diffs[start] {
+                runs.push(diffs[start] * BLOCKS + (i - start - 1), 2);
+                start = i;
+            }
+        }
+        let unchanged = runs.values.len() == 2 && diffs[0] == 8;
+        match repeat {
+            Some(at) if unchanged && out.values[at] < TABLE.len() - 1 => out.values[at] += 1,
+            _ if unchanged => {
+                out.values.extend(runs.values);
+                out.push(0, 1);
+                repeat = Some(out.values.len() - 1);
+            }
+            _ => {
+                out.values.extend(runs.values);
+                repeat = None;
+            }
+        }
+
+        let comment = page.comment != prev_comment;
+        let flags = (!page.lock as usize) << 4 | (comment as usize) << 3 | ((i == 0) as usize) << 2;
+        out.push(encode_piece(&page.piece)? + 32 * BLOCKS * flags, 3);
+
+        if comment {
+            let escaped = escape(&page.comment);
+            let chars: Vec<usize> = escaped
+                .bytes()
+                .map(|b| COMMENT_TABLE.iter().position(|&c| c == b).unwrap())
+                .collect();
+            out.push(chars.len(), 2);
+            for chunk in chars.chunks(4) {
+                let value = chunk.iter().rev().fold(0, |acc, &c| acc * 96 + c);
+                out.push(value, 5);
+            }
+            prev_comment = &page.comment;
+        }
+
+        if page.lock {
+            lock(&mut field, &page.piece)?;
+        }
+        prev = field;
+    }
+
+    let data: String = out.values.iter().map(|&v| TABLE[v] as char).collect();
+    Ok(format!("v115@{}", data))
+}
+
+/// Reads every page of a v115 fumen onto boards `height` rows tall.
+pub fn decode(fumen: &str, height: usize) -> Result<Vec<Page>, FumenError> {
+    let start = fumen.find("115@").ok_or(FumenError::Version)?;
+    let data: Vec<u8> = fumen[start + 4..].bytes().filter(|&c| c != b'?').collect();
+    let mut reader = Reader { data: &data };
+
+    let mut pages = Vec::new();
+    let mut prev = [0; BLOCKS];
+    let mut comment = String::new();
+    let mut repeat = 0;
+
+    while !reader.data.is_empty() {
+        let mut field = prev;
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut i = 0;
+            while i < BLOCKS {
+                let run = reader.poll(2)?;
+                if run == EMPTY_DIFF {
+                    repeat = reader.poll(1)?;
+                }
+                let diff = (run / BLOCKS) as i32 - 8;
+                for _ in 0..=run % BLOCKS {
+                    let cell = field.get_mut(i).ok_or(FumenError::Corrupt)?;
+                    *cell = u8::try_from(*cell as i32 + diff).map_err(|_| FumenError::Corrupt)?;
+                    i += 1;
+                }
+            }
+        }
+
+        let action = reader.poll(3)?;
+        let kind = (action % 8) as u8;
+        let rotation = action / 8 % 4;
+        let position = action / 32 % BLOCKS;
+        let flags = action / (32 * BLOCKS);
+        let piece = match kind {
+            0 => None,
+            _ => Some(decode_piece(kind, rotation, position).ok_or(FumenError::Corrupt)?),
+        };
+
+        if flags >> 3 & 1 == 1 {
+            let len = reader.poll(2)?;
+            let mut escaped = String::new();
+            for chunk in 0..len.div_ceil(4) {
+                let mut value = reader.poll(5)?;
+                for _ in 0..4.min(len - chunk * 4) {
+                    escaped.push(*COMMENT_TABLE.get(value % 96).ok_or(FumenError::Corrupt)? as char);
+                    value /= 96;
+                }
+            }
+            comment = unescape(&escaped);
+        }
+
+        let page = Page {
+            board: to_board(&field, height)?,
+            piece,
+            comment: comment.clone(),
+            lock: flags >> 4 & 1 == 0,
+        };
+        if page.lock {
+            lock(&mut field, &page.piece)?;
+            if flags & 1 == 1 {
+                rise(&mut field);
+            }
+            if flags >> 1 & 1 == 1 {
+                mirror(&mut field);
+            }
+        }
+        prev = field;
+        pages.push(page);
+    }
+    Ok(pages)
+}
+
+/// Starts a game from the first page of `fumen`, with its piece (if any)
+/// as the active piece, where the page has it.
+pub fn load(fumen: &str, height: usize, seed: usize) -> Result<Game, FumenError> {
+    let page = decode(fumen, height)?.into_iter().next().ok_or(FumenError::Corrupt)?;
+    let mut game = Game::new(height, WIDTH, seed);
+    game.board = page.board;
+    if let Some(piece) = page.piece {
+        game.active = piece;
+    }
+    Ok(game)
+}
+
+/// One page per step of `plan`, played from `board`.
+pub fn encode_plan(board: &Board, plan: &Plan) -> Result<String, FumenError> {
+    let mut before = board.clone();
+    let mut pages = Vec::new();
+    for step in plan.steps.iter() {
+        let mut page = Page::new(before, Some(step.placement));
+        let mut notes = Vec::new();
+        if step.hold {
+            notes.push("hold".to_string());
+        }
+        if step.lines_cleared > 0 {
+            notes.push(format!("{} lines", step.lines_cleared));
+        }
+        page.comment = notes.join(", ");
+        pages.push(page);
+        before = step.board.clone();
+    }
+    encode(&pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Z: usize = 0;
+    const O: usize = 2;
+    const S: usize = 3;
+    const I: usize = 4;
+    const T: usize = 6;
+
+    /// One locking page per piece, each drawn on the field the last left.
+    fn pages(pieces: &[Placement]) -> Vec<Page> {
+        let mut board = Board::new(WIDTH, 20);
+        let mut out = Vec::new();
+        for piece in pieces {
+            out.push(Page::new(board.clone(), Some(*piece)));
+            for [row, col] in piece.abs_locations() {
+                board.paint(row as usize, col as usize, piece.piece_type);
+            }
+        }
+        out
+    }
+
+    fn check(pieces: &[Placement], fumen: &str) {
+        assert_eq!(encode(&pages(pieces)).unwrap(), fumen);
+        let decoded = decode(fumen, 20).unwrap();
+        let expected = pages(pieces);
+        assert_eq!(decoded.len(), expected.len());
+        for (page, want) in decoded.iter().zip(expected.iter()) {
+            assert_eq!(page.piece, want.piece);
+            assert_eq!(page.board, want.board);
+        }
+    }
+
+    // the expected strings follow tetris-fumen's encoder, shifts included
+
+    #[test]
+    fn empty_field() {
+        let page = Page::new(Board::new(WIDTH, 20), None);
+        assert_eq!(encode(&[page]).unwrap(), "v115@vhAAgH");
+        let decoded = decode("v115@vhAAgH", 20).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert!(decoded[0].board.is_empty() && decoded[0].piece.is_none());
+    }
+
+    #[test]
+    fn pieces_in_the_top_row() {
+        // pointing down from the highest visible row
+        let decoded = decode("v115@vhAFiH", TOP).unwrap();
+        assert_eq!(decoded[0].piece, Some(Placement::new(T, 2, 22, 4)));
+        // a position in the hidden row under the field holds no piece
+        assert_eq!(decode("v115@vhAVVJ", TOP).err(), Some(FumenError::Corrupt));
+    }
+
+    #[test]
+    fn t_piece() {
+        check(&[Placement::new(T, 0, 0, 4)], "v115@vhAVQJ");
+    }
+
+    #[test]
+    fn o_piece_shifts() {
+        let pieces = [
+            Placement::new(O, 0, 0, 0),
+            Placement::new(O, 3, 1, 9),
+            Placement::new(O, 2, 1, 5),
+        ];
+        check(&pieces, "v115@vhCTJJboBDrB");
+    }
+
+    #[test]
+    fn i_piece_shifts() {
+        check(&[Placement::new(I, 3, 2, 0), Placement::new(I, 2, 0, 3)], "v115@vhBZ/IBvB");
+    }
+
+    #[test]
+    fn s_piece_shifts() {
+        check(&[Placement::new(S, 1, 1, 0), Placement::new(S, 0, 0, 4)], "v115@vhBvJJXrB");
+    }
+
+    #[test]
+    fn z_piece_shifts() {
+        check(&[Placement::new(Z, 3, 1, 9), Placement::new(Z, 0, 0, 5)], "v115@vhBcNJ0rB");
+    }
+
+    #[test]
+    fn non_ascii_comment() {
+        let fumen = "v115@vhAVQYhAlvs2AjxDfETIPSBlvs2AFCEfET4kVBFb85AQo78AVAAAA";
+        let mut page = Page::new(Board::new(WIDTH, 20), Some(Placement::new(T, 0, 0, 4)));
+        page.comment = "テトリス 100%".to_string();
+        assert_eq!(encode(&[page]).unwrap(), fumen);
+        assert_eq!(decode(fumen, 20).unwrap()[0].comment, "テトリス 100%");
+    }
+
+    #[test]
+    fn long_comment_keeps_whole_escapes() {
+        let mut page = Page::new(Board::new(WIDTH, 20), None);
+        page.comment = "あ".repeat(1000);
+        let decoded = decode(&encode(&[page]).unwrap(), 20).unwrap();
+        // six characters per escape, as many as fit in the limit
+        assert_eq!(decoded[0].comment, "あ".repeat(MAX_COMMENT / 6));
+    }
+
+    #[test]
+    fn load_keeps_the_piece_position() {
+        let game = load("v115@vhBvJJXrB", 20, 1).unwrap();
+        assert_eq!(game.active, Placement::new(S, 1, 1, 0));
+    }
+}
@@ -6,18 +6,24 @@ use crate::piece::*;
 use crate::queue::*;
 use std::fmt::{Display, Formatter};
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Game {
     pub board: Board,
     pub queue: Queue,
     pub active: Placement,
     pub hold: Option<usize>,
-    pub placements: Vec<Placement>
+    pub placements: Vec<Placement>,
+    /// hole columns of garbage rows waiting to enter the board, next first
+    pub pending_garbage: VecDeque<usize>,
+    /// lines of attack sent so far, after cancelling
+    pub attack_sent: usize,
 }
 
 impl Display for Game {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Queue; {}\n", self.queue)?;
-        write!(f, "Hold: {:?}\n", self.hold)?;
+        writeln!(f, "Queue; {}", self.queue)?;
+        writeln!(f, "Hold: {:?}", self.hold)?;
         write!(f, "{}", self.board.to_string(&self.active))
     }
 }
@@ -33,14 +39,26 @@ impl Game {
             queue,
             active,
             hold: None,
-            placements: Vec::new()
-
+            placements: Vec::new(),
+            pending_garbage: VecDeque::new(),
+            attack_sent: 0,
         }
     }
 
     pub fn new_piece(&self, piece_type: usize) -> Placement {
         new_piece(piece_type, self.board.height, self.board.width)
     }
+
+    /// Queues garbage rows to enter after the next piece that clears nothing.
+    pub fn receive_garbage(&mut self, holes: impl IntoIterator<Item = usize>) {
+        self.pending_garbage.extend(holes);
+    }
+
+    /// The active piece has nowhere to be, e.g. garbage pushed the stack
+    /// into the spawn area.
+    pub fn topped_out(&self) -> bool {
+        !self.board.piece_valid_location(&self.active)
+    }
 }
 
 fn default_piece_spawn(height: usize, width: usize) -> (i8, i8) {
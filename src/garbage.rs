@@ -0,0 +1,125 @@
+#![allow(dead_code)]
+
+use crate::queue::next_num;
+
+/// Most garbage rows that enter the board on a single piece; the rest stays
+/// pending for the next one.
+pub const GARBAGE_CAP: usize = 8;
+/// Extra lines sent for clearing the whole board.
+pub const PERFECT_CLEAR_BONUS: usize = 10;
+
+/// Lines of attack for a clear, before cancelling anything pending.
+pub fn attack(lines_cleared: usize, perfect_clear: bool) -> usize {
+    let base = match lines_cleared {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        _ => 4,
+    };
+    if perfect_clear {
+        base + PERFECT_CLEAR_BONUS
+    } else {
+        base
+    }
+}
+
+/// How garbage holes are laid out. Each attack gets a fresh hole column, and
+/// every row after the first moves it with probability `messiness`: 0 is
+/// clean garbage with one straight well, 1 is cheese.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GarbageConfig {
+    pub messiness: f32,
+}
+
+impl GarbageConfig {
+    pub fn clean() -> Self {
+        Self { messiness: 0.0 }
+    }
+
+    pub fn cheese() -> Self {
+        Self { messiness: 1.0 }
+    }
+}
+
+impl Default for GarbageConfig {
+    fn default() -> Self {
+        Self::clean()
+    }
+}
+
+/// Picks hole columns with the same generator the queue uses, so a seed
+/// fixes the garbage as well as the pieces.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GarbageGenerator {
+    seed: usize,
+    pub config: GarbageConfig,
+}
+
+impl GarbageGenerator {
+    /// Seed 0 picks the same holes as seed 1.
+    pub fn new(seed: usize, config: GarbageConfig) -> Self {
+        Self {
+            seed: seed.max(1),
+            config,
+        }
+    }
+
+    fn column(&mut self, width: usize) -> usize {
+        ((next_num(&mut self.seed) * width as f32) as usize).min(width - 1)
+    }
+
+    /// Hole columns for an attack of `lines` rows, in the order they enter
+    /// the board, so the last one ends up at the bottom.
+    pub fn holes(&mut self, lines: usize, width: usize) -> Vec<usize> {
+        let mut hole = self.column(width);
+        let mut out = Vec::with_capacity(lines);
+        for row in 0..lines {
+            if row > 0 && width > 1 && next_num(&mut self.seed) < self.config.messiness {
+                // any column but the current one
+                hole = (hole + 1 + self.column(width - 1)) % width;
+            }
+            out.push(hole);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::Queue;
+
+    #[test]
+    fn seed_zero_is_seed_one() {
+        let (mut zero, mut one) = (Queue::new(0), Queue::new(1));
+        for _ in 0..14 {
+            assert_eq!(zero.next(), one.next());
+        }
+        let holes = |seed| GarbageGenerator::new(seed, GarbageConfig::cheese()).holes(10, 10);
+        assert_eq!(holes(0), holes(1));
+    }
+
+    #[test]
+    fn clean_garbage_keeps_one_well() {
+        let mut generator = GarbageGenerator::new(5, GarbageConfig::clean());
+        let holes = generator.holes(6, 10);
+        assert!(holes.iter().all(|&hole| hole == holes[0] && hole < 10));
+    }
+
+    #[test]
+    fn cheese_moves_every_row() {
+        let mut generator = GarbageGenerator::new(5, GarbageConfig::cheese());
+        let holes = generator.holes(20, 10);
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+        assert!(holes.iter().all(|&hole| hole < 10));
+    }
+
+    #[test]
+    fn attack_table() {
+        let sent: Vec<usize> = (0..=4).map(|lines| attack(lines, false)).collect();
+        assert_eq!(sent, [0, 0, 1, 2, 4]);
+        assert_eq!(attack(1, true), PERFECT_CLEAR_BONUS);
+    }
+}
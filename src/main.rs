@@ -4,125 +4,42 @@ mod analyzer;
 mod board;
 mod bot;
 mod control;
+mod diff;
+mod finesse;
+mod fumen;
 mod game;
+mod garbage;
+mod pc;
 mod piece;
+mod plan;
+mod player;
 mod queue;
+mod reachability;
+mod render;
+mod replay;
+mod schedule;
+mod sequence;
+#[cfg(feature = "server")]
+mod server;
+mod tbp;
+mod text;
+mod tiling;
+mod timing;
+mod ttr;
+mod versus;
+mod viewport;
 
 use crate::control::{Command, Executable, PlacementActions};
 use board::Board;
 use analyzer::*;
 use bot::*;
+use diff::*;
 use std::collections::HashSet;
 use std::time::{Duration, Instant};
 
 fn l_spin_bot() -> Bot {
     let mut bot = Bot::new(20, 10, 43);
-    let board = vec![
-        (0, 0),
-        (1, 0),
-        (2, 0),
-        (3, 0),
-        (4, 0),
-        (5, 0),
-        (6, 0),
-        (7, 0),
-        (8, 0),
-        (9, 0),
-        (10, 0),
-        (11, 0),
-        (12, 0),
-        (13, 0),
-        (14, 0),
-        (4, 1),
-        (5, 1),
-        (6, 1),
-        (7, 1),
-        (8, 1),
-        (9, 1),
-        (10, 1),
-        (11, 1),
-        (12, 1),
-        (14, 1),
-        (1, 2),
-        (2, 2),
-        (5, 2),
-        (6, 2),
-        (7, 2),
-        (8, 2),
-        (9, 2),
-        (0, 3),
-        (1, 3),
-        (6, 3),
-        (7, 3),
-        (8, 3),
-        (9, 3),
-        (11, 3),
-        (12, 3),
-        (0, 4),
-        (1, 4),
-        (3, 4),
-        (4, 4),
-        (6, 4),
-        (9, 4),
-        (12, 4),
-        (0, 5),
-        (1, 5),
-        (2, 5),
-        (3, 5),
-        (4, 5),
-        (12, 5),
-        (0, 6),
-        (1, 6),
-        (2, 6),
-        (3, 6),
-        (4, 6),
-        (5, 6),
-        (6, 6),
-        (7, 6),
-        (9, 6),
-        (10, 6),
-        (11, 6),
-        (12, 6),
-        (0, 7),
-        (1, 7),
-        (2, 7),
-        (3, 7),
-        (4, 7),
-        (5, 7),
-        (6, 7),
-        (7, 7),
-        (9, 7),
-        (10, 7),
-        (11, 7),
-        (12, 7),
-        (0, 8),
-        (1, 8),
-        (2, 8),
-        (3, 8),
-        (4, 8),
-        (5, 8),
-        (6, 8),
-        (7, 8),
-        (8, 8),
-        (9, 8),
-        (10, 8),
-        (11, 8),
-        (12, 8),
-        (0, 9),
-        (1, 9),
-        (2, 9),
-        (3, 9),
-        (4, 9),
-        (5, 9),
-        (6, 9),
-        (7, 9),
-        (8, 9),
-        (9, 9),
-        (10, 9),
-        (11, 9),
-        (12, 9),
-    ];
-    bot.game.board.bulk_add(board);
+    bot.game.board = include_str!("../fixtures/l_spin.txt").parse().unwrap();
     bot
 }
 
@@ -182,8 +99,7 @@ fn test() {
     //     bot.undo();
     // }
 
-    loop {
-        let action = bot.best_action(2, 30, &board);
+    while let Some(action) = bot.best_action(2, 30, &board) {
         bot.action(action);
         println!("{}", bot.game.placements.len());
         if score(&bot.game.placements, &board) > 10 {
@@ -193,10 +109,46 @@ fn test() {
         bot.game.placements.clear();
         println!("{}", bot);
     }
+
+    println!("{}", diff_string(&board, &bot.game.board));
+    println!("{}", compare(&board, &bot.game.board));
 }
 
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
+    // `tbp [target.txt]` plays the Tetris Bot Protocol on stdin/stdout
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("tbp") {
+        let target = match args.get(2).map(|path| (path, std::fs::read_to_string(path))) {
+            None => None,
+            Some((_, Ok(text))) => match text.parse() {
+                Ok(board) => Some(board),
+                Err(error) => {
+                    eprintln!("tbp: bad target board: {:?}", error);
+                    std::process::exit(1);
+                }
+            },
+            Some((path, Err(error))) => {
+                eprintln!("tbp: could not read {}: {}", path, error);
+                std::process::exit(1);
+            }
+        };
+        tbp::TbpBot::new(target).run();
+        return;
+    }
+    #[cfg(feature = "server")]
+    if args.get(1).map(String::as_str) == Some("serve") {
+        let port = match args.get(2).map(|port| port.parse()) {
+            None => 8080,
+            Some(Ok(port)) => port,
+            Some(Err(_)) => {
+                eprintln!("usage: {} serve [port]", args[0]);
+                std::process::exit(1);
+            }
+        };
+        serve_frames(port);
+        return;
+    }
     test()
     // let board = to_board(load_image(210));
     // println!("{}", board);
@@ -282,7 +234,55 @@ fn move_benchmark() {
 fn some_frame_thing() {
     for frame in 0..=20 {
         let img = load_image(frame * 30);
-        println!("{}", to_board(img));
+        player::redraw(&format!("frame {}\n{}", frame * 30, to_board(img)));
         std::thread::sleep(std::time::Duration::from_millis(500));
     }
 }
+
+fn sequence_frames() {
+    let targets: Vec<Board> = (0..=20).map(|frame| to_board(load_image(frame * 30))).collect();
+    let mut sequencer = sequence::Sequencer::new(Bot::new(40, 40, 1), Default::default());
+    for target in targets.iter() {
+        let report = sequencer.next_frame(target);
+        println!(
+            "frame {} ({:?}): {} pieces, {} lines, {}",
+            report.frame, report.transition, report.pieces, report.lines_cleared, report.comparison
+        );
+    }
+}
+
+/// Like `sequence_frames`, with buffer rows over the picture so pieces can
+/// always spawn above it.
+fn sequence_frames_tall() {
+    let targets: Vec<Board> = (0..=20).map(|frame| to_board(load_image(frame * 30))).collect();
+    let viewport = viewport::Viewport::bottom(&targets[0]);
+    let (height, width) = viewport.field(viewport::BUFFER_ROWS);
+    let config = sequence::SequenceConfig {
+        viewport: Some(viewport),
+        ..Default::default()
+    };
+    let mut sequencer = sequence::Sequencer::new(Bot::new(height, width, 1), config);
+    for target in targets.iter() {
+        let report = sequencer.next_frame(target);
+        println!(
+            "frame {} ({:?}): {} pieces, {} lines, {}",
+            report.frame, report.transition, report.pieces, report.lines_cleared, report.comparison
+        );
+    }
+}
+
+/// Paints the video frames while streaming every placement to the viewer
+/// page at http://127.0.0.1:`port`.
+#[cfg(feature = "server")]
+fn serve_frames(port: u16) {
+    let server = server::Server::bind(("127.0.0.1", port)).unwrap();
+    println!("watching on http://127.0.0.1:{}", server.port);
+    let mut sequencer = sequence::Sequencer::new(Bot::new(40, 40, 1), Default::default());
+    sequencer.observe(move |game, target| {
+        server.publish_game(game, Some(target));
+        std::thread::sleep(Duration::from_millis(50));
+    });
+    for frame in 0..=20 {
+        sequencer.next_frame(&to_board(load_image(frame * 30)));
+    }
+}
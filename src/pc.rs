@@ -0,0 +1,351 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::bot::Bot;
+use crate::control::{Command, Executable, HardDrop};
+use crate::fumen::{encode_plan, FumenError};
+use crate::game::Game;
+use crate::piece::Point;
+use crate::plan::Plan;
+use std::collections::HashSet;
+
+#[derive(Copy, Clone, Debug)]
+pub struct PcConfig {
+    /// tallest clear to look for, e.g. 2, 4 or 6
+    pub max_lines: usize,
+    /// stop once this many solutions are found
+    pub max_solutions: usize,
+}
+
+impl Default for PcConfig {
+    fn default() -> Self {
+        Self {
+            max_lines: 4,
+            max_solutions: usize::MAX,
+        }
+    }
+}
+
+/// The tallest clear of at most `max_lines` rows that covers the stack and
+/// leaves a multiple of four empty cells, since only whole pieces fill it.
+pub fn pc_height(board: &Board, max_lines: usize) -> Option<usize> {
+    let filled = board.arr.iter().flatten().filter(|&&cell| cell).count();
+    let stack = (0..board.height)
+        .rev()
+        .find(|&row| board.arr[row].iter().any(|&cell| cell))
+        .map_or(0, |row| row + 1);
+    (stack.max(1)..=max_lines.min(board.height))
+        .rev()
+        .find(|&lines| (lines * board.width - filled).is_multiple_of(4))
+}
+
+/// Every pocket of empty cells below `limit` has to take a whole number of
+/// pieces, otherwise some cell can never be filled. Only the size is
+/// checked: a pocket no piece can reach or fit still passes, and is left to
+/// the search to run out of placements.
+fn fillable(board: &Board, limit: usize) -> bool {
+    let mut seen = vec![vec![false; board.width]; limit];
+    for row in 0..limit {
+        for col in 0..board.width {
+            if seen[row][col] || board.get(row, col) {
+                continue;
+            }
+            seen[row][col] = true;
+            let mut size = 0usize;
+            let mut stack = vec![(row, col)];
+            while let Some((r, c)) = stack.pop() {
+                size += 1;
+                let neighbours = [
+                    (r + 1, c),
+                    (r.wrapping_sub(1), c),
+                    (r, c + 1),
+                    (r, c.wrapping_sub(1)),
+                ];
+                for (nr, nc) in neighbours {
+                    if nr < limit && nc < board.width && !seen[nr][nc] && !board.get(nr, nc) {
+                        seen[nr][nc] = true;
+                        stack.push((nr, nc));
+                    }
+                }
+            }
+            if !size.is_multiple_of(4) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Whether pieces can even out `imbalance` pairs of cells between two
+/// colours when each of `one` shifts it by exactly one pair either way,
+/// each of `maybe` by one pair or none, and each of `two` by two or none.
+fn balances(imbalance: usize, one: usize, maybe: usize, two: usize) -> bool {
+    imbalance <= one + maybe + 2 * two && (maybe > 0 || imbalance % 2 == one % 2)
+}
+
+/// Piece counts for the rest of a clear that needs `pieces` more: hold, the
+/// active piece and the queue, one more than needed, since hold lets any
+/// one of them sit out.
+fn pool(game: &Game, pieces: usize) -> [usize; 7] {
+    let mut counts = [0; 7];
+    counts[game.active.piece_type] += 1;
+    if let Some(hold) = game.hold {
+        counts[hold] += 1;
+    }
+    let mut queue = game.queue.clone();
+    for _ in usize::from(game.hold.is_some())..pieces {
+        counts[queue.next()] += 1;
+    }
+    counts
+}
+
+/// Parity pruning on the empty cells below `limit`, for every choice of the
+/// piece that sits out.
+///
+/// Columns coloured alternately: O, S and Z always cover two of each
+/// colour, L and J three of one, T either, and I two of each or four of one.
+/// Clears never move a cell to another column, so this holds throughout.
+///
+/// A checkerboard: only T covers three of one colour. A clear shifts the
+/// rows above it and can split a later piece across the cleared row, which
+/// breaks this, except when at most two rows are left and nothing can be
+/// placed on both sides of a clear.
+fn parity(game: &Game, limit: usize) -> bool {
+    let board = &game.board;
+    let (mut columns, mut squares) = ([0usize; 2], [0usize; 2]);
+    for row in 0..limit {
+        for col in (0..board.width).filter(|&col| !board.get(row, col)) {
+            columns[col % 2] += 1;
+            squares[(row + col) % 2] += 1;
+        }
+    }
+    let pieces = (columns[0] + columns[1]) / 4;
+    let columns = columns[0].abs_diff(columns[1]) / 2;
+    let squares = squares[0].abs_diff(squares[1]) / 2;
+
+    let pool = pool(game, pieces);
+    (0..7).filter(|&out| pool[out] > 0).any(|out| {
+        let mut used = pool;
+        used[out] -= 1;
+        let [_, l, _, _, i, j, t] = used;
+        balances(columns, l + j, t, i) && (limit > 2 || balances(squares, t, 0, 0))
+    })
+}
+
+/// Rows below the clear, active piece, hold and pieces placed: together
+/// they fix everything the rest of the search depends on.
+type State = (Vec<Vec<bool>>, usize, Option<usize>, usize);
+
+struct Search {
+    bot: Bot,
+    config: PcConfig,
+    /// states already searched without finding anything
+    dead: HashSet<State>,
+    plan: Plan,
+    out: Vec<Plan>,
+}
+
+impl Search {
+    fn done(&self) -> bool {
+        self.out.len() >= self.config.max_solutions
+    }
+
+    fn search(&mut self, limit: usize) -> bool {
+        let game = &self.bot.game;
+        let state = (
+            game.board.arr[..limit].to_vec(),
+            game.active.piece_type,
+            game.hold,
+            self.plan.steps.len(),
+        );
+        if self.dead.contains(&state) {
+            return false;
+        }
+
+        let mut found = false;
+        // rotations with the same cells would only repeat solutions
+        let mut tried: HashSet<(bool, [Point; 4])> = HashSet::new();
+        for mut actions in self.bot.placements() {
+            if self.done() {
+                break;
+            }
+            let cells = actions.placement.last().unwrap().abs_locations();
+            let hold = matches!(actions.batch.commands.front(), Some(Command::Hold(_)));
+            if cells.iter().any(|&[row, _]| row as usize >= limit) || !tried.insert((hold, cells)) {
+                continue;
+            }
+
+            actions.push(HardDrop::new().into());
+            let step = self.bot.step(&mut actions);
+            let below = limit - step.lines_cleared;
+            let cleared = step.board.is_empty();
+            self.plan.steps.push(step);
+
+            if cleared {
+                self.out.push(self.plan.clone());
+                found = true;
+            } else if fillable(&self.bot.game.board, below)
+                && parity(&self.bot.game, below)
+                && self.search(below)
+            {
+                found = true;
+            }
+
+            self.plan.steps.pop();
+            actions.undo(&mut self.bot.game);
+        }
+
+        if !found {
+            self.dead.insert(state);
+        }
+        found
+    }
+}
+
+/// Every placement sequence from `game` that empties the board without
+/// building above `config.max_lines` rows, using hold freely. Pending
+/// garbage is ignored. Solutions come in the order the search finds them.
+///
+/// Boards are pruned on pocket sizes and parity only; cells no placement
+/// can reach are found by the move generator running out of moves.
+pub fn perfect_clears(game: &Game, config: &PcConfig) -> Vec<Plan> {
+    let Some(limit) = pc_height(&game.board, config.max_lines) else {
+        return Vec::new();
+    };
+    if !fillable(&game.board, limit) || !parity(game, limit) {
+        return Vec::new();
+    }
+
+    let mut bot = Bot::new(game.board.height, game.board.width, 1);
+    bot.game = game.clone();
+    bot.game.pending_garbage.clear();
+    let mut search = Search {
+        bot,
+        config: *config,
+        dead: HashSet::new(),
+        plan: Plan::new(),
+        out: Vec::new(),
+    };
+    search.search(limit);
+    search.out
+}
+
+/// The first perfect clear found, if there is one.
+pub fn perfect_clear(game: &Game, max_lines: usize) -> Option<Plan> {
+    let config = PcConfig {
+        max_lines,
+        max_solutions: 1,
+    };
+    perfect_clears(game, &config).pop()
+}
+
+/// One fumen per solution, each starting from `board`.
+pub fn to_fumen(board: &Board, plans: &[Plan]) -> Result<Vec<String>, FumenError> {
+    plans.iter().map(|plan| encode_plan(board, plan)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::{piece_from_name, piece_name};
+
+    /// Columns `wall..` empty in the bottom `rows` rows, the rest filled,
+    /// with `queue` dealt first.
+    fn well(rows: usize, wall: usize, queue: &str) -> Game {
+        let mut game = Game::new(20, 10, 1);
+        for row in 0..rows {
+            for col in 0..wall {
+                game.board.add(row, col);
+            }
+        }
+        let mut pieces = queue.chars().map(|c| piece_from_name(c).unwrap());
+        game.active = game.new_piece(pieces.next().unwrap());
+        game.queue.pieces = pieces.chain(game.queue.pieces.iter().copied()).collect();
+        game
+    }
+
+    /// Each step plays the piece hold or the queue gives it.
+    fn follows_the_queue(game: &Game, plan: &Plan) -> bool {
+        let (mut current, mut hold) = (game.active.piece_type, game.hold);
+        let mut queue = game.queue.clone();
+        for step in &plan.steps {
+            if step.hold {
+                current = hold.replace(current).unwrap_or_else(|| queue.next());
+            }
+            if step.placement.piece_type != current {
+                return false;
+            }
+            current = queue.next();
+        }
+        true
+    }
+
+    #[test]
+    fn balancing() {
+        // two T pieces can cancel each other out, or add up to two pairs
+        assert!(balances(0, 2, 0, 0) && balances(2, 2, 0, 0));
+        assert!(!balances(1, 2, 0, 0) && !balances(4, 2, 0, 0));
+        // a piece that may or may not shift makes any parity work
+        assert!(balances(1, 2, 1, 0));
+        assert!(balances(3, 1, 0, 1) && !balances(2, 1, 0, 1));
+    }
+
+    #[test]
+    fn four_line_clear_in_a_well() {
+        let game = well(4, 6, "IOJLS");
+        let plans = perfect_clears(&game, &PcConfig::default());
+        assert!(!plans.is_empty());
+        let mut seen = HashSet::new();
+        for plan in &plans {
+            // four pieces fill the sixteen cells, dealt in order through hold
+            assert_eq!(plan.steps.len(), 4);
+            assert!(follows_the_queue(&game, plan));
+            let lines: usize = plan.steps.iter().map(|step| step.lines_cleared).sum();
+            assert_eq!(lines, 4);
+            assert!(plan.steps.last().unwrap().board.is_empty());
+            let placements: Vec<_> = plan
+                .steps
+                .iter()
+                .map(|step| (step.hold, step.placement.abs_locations()))
+                .collect();
+            assert!(seen.insert(placements));
+        }
+
+        // I flat on the bottom, then O, J and L stand up in what is left
+        let first = &plans[0];
+        let pieces: String = first
+            .steps
+            .iter()
+            .map(|step| piece_name(step.placement.piece_type))
+            .collect();
+        assert_eq!(pieces, "IOJL");
+        let lines: Vec<usize> = first.steps.iter().map(|step| step.lines_cleared).collect();
+        assert_eq!(lines, [1, 0, 0, 3]);
+        assert_eq!(
+            to_fumen(&game.board, &plans[..1]).unwrap(),
+            ["v115@9gF8DeF8DeF8DeF8NexRYJAxno2AsOprDTBAAAvhCzsQAAOsB6tQJAzno2AsOprDTBAAA"]
+        );
+    }
+
+    #[test]
+    fn parity_rules_out_a_box() {
+        // T, L and I cannot fill a 4 by 2 box two at a time: with T the
+        // checkerboard is off by one pair, and L with I leaves the columns odd
+        let game = well(2, 6, "TLI");
+        assert!(!parity(&game, 2));
+        assert!(perfect_clear(&game, 2).is_none());
+
+        let game = well(2, 6, "TOO");
+        assert!(parity(&game, 2));
+        assert!(perfect_clear(&game, 2).is_some());
+    }
+
+    #[test]
+    fn odd_cells_have_no_clear() {
+        let mut game = well(2, 6, "IIOO");
+        game.board.add(1, 9);
+        // 7 empty cells in two rows, 17 in four: neither splits into pieces
+        assert_eq!(pc_height(&game.board, 4), None);
+        assert!(perfect_clears(&game, &PcConfig::default()).is_empty());
+    }
+}
@@ -5,7 +5,18 @@ pub type Point = [i8; 2];
 use crate::piece::data::PieceLocations;
 use data::{Offset, PieceLocation, OFFSETS, PIECES};
 
+const PIECE_NAMES: [char; 7] = ['Z', 'L', 'O', 'S', 'I', 'J', 'T'];
+
+pub fn piece_name(piece_type: usize) -> char {
+    PIECE_NAMES[piece_type]
+}
+
+pub fn piece_from_name(name: char) -> Option<usize> {
+    PIECE_NAMES.iter().position(|&c| c == name.to_ascii_uppercase())
+}
+
 #[derive(Copy, Clone, Debug, Default, Hash, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Placement {
     pub piece_type: usize,
     pub rotation_state: usize,
@@ -27,6 +38,12 @@ impl Placement {
         PIECES[self.piece_type][self.rotation_state]
     }
 
+    pub fn abs_locations(&self) -> PieceLocation {
+        let mut out = self.rel_locations().map(|[r, c]| [r + self.row, c + self.col]);
+        out.sort();
+        out
+    }
+
     pub fn shift(&mut self, y: i8, x: i8) {
         self.row += y;
         self.col += x;
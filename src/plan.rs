@@ -0,0 +1,138 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::control::*;
+use crate::piece::{piece_name, Placement};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+/// One piece of a `Plan`: where it lands, the inputs that get it there and
+/// the board as it looks once the piece has locked and lines have cleared.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Step {
+    pub placement: Placement,
+    pub inputs: Vec<Command>,
+    pub hold: bool,
+    pub lines_cleared: usize,
+    pub board: Board,
+}
+
+impl Step {
+    pub fn command(&self) -> Command {
+        Batch {
+            commands: VecDeque::from(self.inputs.clone()),
+        }
+        .into()
+    }
+}
+
+/// A line found by `Bot::look_ahead`, in the order the pieces are placed.
+/// `evaluation` is the summed `score` of every placement, so lower is better.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plan {
+    pub steps: Vec<Step>,
+    pub evaluation: i32,
+}
+
+impl Plan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn first(&self) -> Option<&Step> {
+        self.steps.first()
+    }
+
+    pub fn placements(&self) -> Vec<Placement> {
+        self.steps.iter().map(|step| step.placement).collect()
+    }
+
+    pub fn lines_cleared(&self) -> usize {
+        self.steps.iter().map(|step| step.lines_cleared).sum()
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Evaluation: {}", self.evaluation)?;
+        for (i, step) in self.steps.iter().enumerate() {
+            let Placement {
+                piece_type,
+                rotation_state,
+                row,
+                col,
+            } = step.placement;
+            write!(
+                f,
+                "{}: {} r{} ({}, {})",
+                i,
+                piece_name(piece_type),
+                rotation_state,
+                row,
+                col
+            )?;
+            if step.hold {
+                write!(f, " [hold]")?;
+            }
+            if step.lines_cleared > 0 {
+                write!(f, " [{} lines]", step.lines_cleared)?;
+            }
+            writeln!(f, " - {} inputs", step.inputs.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Bot;
+    use crate::game::Game;
+
+    fn step(placement: Placement, hold: bool, lines_cleared: usize) -> Step {
+        Step {
+            placement,
+            inputs: vec![HardDrop::new().into()],
+            hold,
+            lines_cleared,
+            board: Board::new(10, 20),
+        }
+    }
+
+    #[test]
+    fn steps_replay_on_a_fresh_game() {
+        let mut target = Board::new(10, 20);
+        target.bulk_add((0..4).map(|col| (0, col)).collect());
+        let plan = Bot::new(20, 10, 1).best_plan(2, 4, &target).unwrap();
+
+        let mut game = Game::new(20, 10, 1);
+        for step in plan.steps.iter() {
+            step.command().execute(&mut game);
+            assert_eq!(game.board, step.board);
+        }
+        for placement in plan.placements() {
+            for [row, col] in placement.abs_locations() {
+                let kind = game.board.kind(row as usize, col as usize);
+                assert_eq!(kind, Some(placement.piece_type));
+            }
+        }
+    }
+
+    #[test]
+    fn display_lists_the_steps() {
+        let plan = Plan {
+            steps: vec![
+                step(Placement::new(2, 0, 0, 0), false, 0),
+                step(Placement::new(4, 1, 1, 9), true, 2),
+            ],
+            evaluation: -6,
+        };
+        assert_eq!(plan.lines_cleared(), 2);
+        assert_eq!(
+            plan.to_string(),
+            "Evaluation: -6\n0: O r0 (0, 0) - 1 inputs\n1: I r1 (1, 9) [hold] [2 lines] - 1 inputs\n"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+#![allow(dead_code)]
+
+use crate::control::Executable;
+use crate::game::Game;
+use crate::piece::piece_name;
+use crate::plan::Step;
+use std::io::{stdin, stdout, BufRead, Write};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+const HOME: &str = "\x1b[H";
+const CLEAR: &str = "\x1b[2J";
+const CLEAR_BELOW: &str = "\x1b[J";
+const HIDE_CURSOR: &str = "\x1b[?25l";
+const SHOW_CURSOR: &str = "\x1b[?25h";
+const PREVIEWS: usize = 5;
+
+/// Draws `screen` over whatever the last call drew instead of scrolling.
+pub fn redraw(screen: &str) {
+    let mut out = stdout().lock();
+    // every line is cleared to its end, in case the last screen was wider
+    let screen = screen.replace('\n', "\x1b[K\n");
+    let _ = write!(out, "{}{}{}", HOME, screen, CLEAR_BELOW);
+    let _ = out.flush();
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Control {
+    Pause,
+    Step,
+    Quit,
+}
+
+fn controls() -> Receiver<Control> {
+    let (send, receive) = channel();
+    thread::spawn(move || {
+        for line in stdin().lock().lines().map_while(Result::ok) {
+            let control = match line.trim() {
+                "p" | "" => Control::Pause,
+                "s" | "n" => Control::Step,
+                "q" => Control::Quit,
+                _ => continue,
+            };
+            if send.send(control).is_err() {
+                break;
+            }
+        }
+    });
+    receive
+}
+
+/// Replays recorded placements onto `game` at `fps` placements per second.
+/// Type `p` (or just enter) to pause, `s` to step while paused and `q` to
+/// quit, each followed by enter.
+pub struct Player {
+    pub game: Game,
+    steps: Vec<Step>,
+    fps: f32,
+    frame: usize,
+    paused: bool,
+}
+
+impl Player {
+    /// `None` unless `fps` is a positive, finite rate.
+    pub fn new(game: Game, steps: Vec<Step>, fps: f32) -> Option<Self> {
+        if !(fps > 0.0 && fps.is_finite()) {
+            return None;
+        }
+        Some(Self {
+            game,
+            steps,
+            fps,
+            frame: 0,
+            paused: false,
+        })
+    }
+
+    fn screen(&self) -> String {
+        let queue: String = self
+            .game
+            .queue
+            .pieces
+            .iter()
+            .take(PREVIEWS)
+            .map(|&piece| piece_name(piece))
+            .collect();
+        let hold = self.game.hold.map_or('-', piece_name);
+        let state = if self.paused { " [paused]" } else { "" };
+        format!(
+            "frame {}/{}{}\nhold: {}  next: {}\n\n{}",
+            self.frame,
+            self.steps.len(),
+            state,
+            hold,
+            queue,
+            self.game.board.to_string(&self.game.active)
+        )
+    }
+
+    fn advance(&mut self) -> bool {
+        match self.steps.get(self.frame) {
+            Some(step) => {
+                step.command().execute(&mut self.game);
+                self.frame += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn play(&mut self) {
+        let controls = controls();
+        let delay = Duration::from_secs_f32(1.0 / self.fps);
+        print!("{}{}", CLEAR, HIDE_CURSOR);
+
+        loop {
+            redraw(&self.screen());
+            let wait = if self.paused { Duration::MAX } else { delay };
+            match controls.recv_timeout(wait) {
+                Ok(Control::Quit) => break,
+                Ok(Control::Pause) => self.paused = !self.paused,
+                Ok(Control::Step) => {
+                    self.advance();
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    if !self.advance() {
+                        break;
+                    }
+                }
+                // stdin closed, keep playing without controls
+                Err(RecvTimeoutError::Disconnected) => {
+                    if self.paused || !self.advance() {
+                        break;
+                    }
+                    thread::sleep(delay);
+                }
+            }
+        }
+
+        redraw(&self.screen());
+        print!("{}", SHOW_CURSOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::Bot;
+
+    #[test]
+    fn rejects_rates_without_a_delay() {
+        for fps in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(Player::new(Game::new(20, 10, 1), Vec::new(), fps).is_none());
+        }
+    }
+
+    #[test]
+    fn advance_replays_the_steps() {
+        let mut bot = Bot::new(20, 10, 1);
+        let plan = bot
+            .best_plan_by(2, 4, &|step| step.placement.row as i32)
+            .unwrap();
+        let mut player = Player::new(Game::new(20, 10, 1), plan.steps.clone(), 2.0).unwrap();
+        while player.advance() {}
+        assert_eq!(player.frame, plan.steps.len());
+        assert_eq!(player.game.board, plan.steps.last().unwrap().board);
+    }
+}
@@ -1,11 +1,19 @@
 use std::collections::VecDeque;
 use std::fmt::{Display, Formatter};
 
+/// Steps the Park-Miller generator behind the queue and garbage holes, and
+/// returns a number in `[0, 1)`. A seed of 0 would stay 0, so it must not be.
+pub(crate) fn next_num(seed: &mut usize) -> f32 {
+    const M: usize = 2147483647;
+    *seed = 16807 * *seed % M;
+    (*seed - 1) as f32 / M as f32
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Queue {
     seed: usize,
     pub pieces: VecDeque<usize>,
-    a: usize,
-    m: usize,
 }
 
 impl Display for Queue {
@@ -15,12 +23,11 @@ impl Display for Queue {
 }
 
 impl Queue {
+    /// Seed 0 deals the same pieces as seed 1.
     pub fn new(seed: usize) -> Self {
         Self {
-            seed,
+            seed: seed.max(1),
             pieces: VecDeque::new(),
-            a: 16807,
-            m: 2147483647,
         }
     }
 
@@ -32,21 +39,20 @@ impl Queue {
         self.pieces.pop_front().unwrap()
     }
 
-    pub fn push(&mut self, piece: usize) {
-        self.pieces.push_front(piece)
+    /// The generator's state; a queue made from it deals whatever follows
+    /// the pieces already in `pieces`.
+    pub fn state(&self) -> usize {
+        self.seed
     }
 
-    fn next_num(&mut self) -> f32 {
-        self.seed = self.a * self.seed % self.m;
-
-        let out = (self.seed - 1) as f32 / self.m as f32;
-        out
+    pub fn push(&mut self, piece: usize) {
+        self.pieces.push_front(piece)
     }
 
     fn seven_bag(&mut self) {
         let mut arr = [0, 1, 2, 3, 4, 5, 6];
         for i in (1..7).rev() {
-            let r = (self.next_num() * (i as f32 + 1.0)) as usize;
+            let r = (next_num(&mut self.seed) * (i as f32 + 1.0)) as usize;
             (arr[i], arr[r]) = (arr[r], arr[i])
         }
         self.pieces.extend(arr.iter());
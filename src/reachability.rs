@@ -0,0 +1,234 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::control::{Command, Executable};
+use crate::finesse::{InputModel, Key};
+use crate::game::{new_piece, Game};
+use crate::piece::Placement;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+
+/// One key of a `Path` and where it leaves the piece.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Move {
+    pub key: Key,
+    pub to: Placement,
+    /// for rotations, the offset test that let it through; anything above
+    /// 0 is a kick
+    pub kick: usize,
+}
+
+/// How a piece gets from spawn onto the target, shortest first. Hard
+/// dropping after the last move locks it at `placement`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Path {
+    pub spawn: Placement,
+    pub moves: Vec<Move>,
+    /// where the piece locks; the target's cells, though its rotation
+    /// state may differ for pieces that look the same turned around
+    pub placement: Placement,
+}
+
+impl Path {
+    /// Every key, ending in `Key::HardDrop`.
+    pub fn keys(&self) -> Vec<Key> {
+        let mut out: Vec<Key> = self.moves.iter().map(|step| step.key).collect();
+        out.push(Key::HardDrop);
+        out
+    }
+
+    pub fn commands(&self) -> Vec<Command> {
+        self.keys().into_iter().map(Key::command).collect()
+    }
+
+    /// The rotations that only went through thanks to a kick.
+    pub fn kicks(&self) -> impl Iterator<Item = &Move> {
+        self.moves.iter().filter(|step| step.kick > 0)
+    }
+}
+
+/// Why a target can't be reached.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Obstacle {
+    /// the piece has no room to spawn
+    SpawnBlocked,
+    /// part of the target is off the board
+    OutOfBounds,
+    /// these cells of the target are already filled
+    Occupied(Vec<(usize, usize)>),
+    /// nothing under the target, so the piece would fall straight through
+    Floating,
+    /// every position the piece can get to from spawn was tried; `closest`
+    /// is the spot it can lock in that shares the most cells with the target
+    Enclosed {
+        explored: usize,
+        closest: Option<Placement>,
+    },
+}
+
+/// Whether a fresh piece of the target's type can lock exactly on
+/// `target`'s cells, and the shortest way there using the keys `model`
+/// allows. Rotations kick as they do in play.
+pub fn reachable(board: &Board, target: &Placement, model: &InputModel) -> Result<Path, Obstacle> {
+    let spawn = new_piece(target.piece_type, board.height, board.width);
+    reachable_from(board, spawn, target, model)
+}
+
+pub fn reachable_from(
+    board: &Board,
+    spawn: Placement,
+    target: &Placement,
+    model: &InputModel,
+) -> Result<Path, Obstacle> {
+    if !board.piece_in_bounds(target) {
+        return Err(Obstacle::OutOfBounds);
+    }
+    let occupied: Vec<(usize, usize)> = target
+        .abs_locations()
+        .iter()
+        .map(|&[row, col]| (row as usize, col as usize))
+        .filter(|&(row, col)| board.get(row, col))
+        .collect();
+    if !occupied.is_empty() {
+        return Err(Obstacle::Occupied(occupied));
+    }
+    if !board.piece_grounded(target) {
+        return Err(Obstacle::Floating);
+    }
+    if !board.piece_valid_location(&spawn) {
+        return Err(Obstacle::SpawnBlocked);
+    }
+
+    let mut game = Game::new(board.height, board.width, 1);
+    game.board = board.clone();
+    let goal = target.abs_locations();
+    let keys = model.keys();
+
+    // breadth first, so the first time a state is seen it was by the shortest path
+    let mut parents: HashMap<Placement, Option<(Placement, Move)>> = HashMap::from([(spawn, None)]);
+    let mut frontier = VecDeque::from([spawn]);
+    let mut closest: Option<(usize, Placement)> = None;
+    while let Some(state) = frontier.pop_front() {
+        let landing = board.ghost(&state);
+        let cells = landing.abs_locations();
+        if cells == goal {
+            let mut moves = Vec::new();
+            let mut cur = state;
+            while let Some((prev, step)) = parents[&cur] {
+                moves.push(step);
+                cur = prev;
+            }
+            moves.reverse();
+            return Ok(Path {
+                spawn,
+                moves,
+                placement: landing,
+            });
+        }
+        let shared = cells.iter().filter(|cell| goal.contains(cell)).count();
+        if closest.is_none_or(|(best, _)| shared > best) {
+            closest = Some((shared, landing));
+        }
+
+        for &key in keys.iter() {
+            game.active = state;
+            let mut command = key.command();
+            command.execute(&mut game);
+            let kick = match &command {
+                Command::PieceRotate(rotate) => rotate.kick(),
+                _ => 0,
+            };
+            if let Entry::Vacant(entry) = parents.entry(game.active) {
+                entry.insert(Some((
+                    state,
+                    Move {
+                        key,
+                        to: game.active,
+                        kick,
+                    },
+                )));
+                frontier.push_back(game.active);
+            }
+        }
+    }
+
+    Err(Obstacle::Enclosed {
+        explored: parents.len(),
+        closest: closest.map(|(_, placement)| placement),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_targets_drop_straight_in() {
+        let board = Board::new(10, 20);
+        let target = Placement::new(2, 0, 0, 0);
+        let path = reachable(&board, &target, &InputModel::default()).unwrap();
+        assert_eq!(path.placement, target);
+        assert_eq!(path.keys(), vec![Key::DasLeft, Key::HardDrop]);
+        assert_eq!(path.kicks().count(), 0);
+    }
+
+    #[test]
+    fn a_tucked_t_needs_a_kick() {
+        // a T slot three rows deep in column 3 with its nub under a roof,
+        // so no straight drop or unkicked turn lands on it
+        let mut board = Board::new(10, 20);
+        for col in (0..10).filter(|&col| col != 3) {
+            board.add(0, col);
+            board.add(2, col);
+            if col != 4 {
+                board.add(1, col);
+            }
+        }
+        board.add(3, 4);
+        let target = Placement::new(6, 1, 1, 3);
+
+        let path = reachable(&board, &target, &InputModel::default()).unwrap();
+        assert_eq!(path.placement, target);
+        let last = path.moves.last().unwrap();
+        assert!(last.kick > 0 && last.to == target);
+        assert_eq!(path.kicks().count(), 1);
+
+        // replaying the keys from spawn locks the T on the slot
+        let mut game = Game::new(20, 10, 1);
+        game.board = board;
+        game.active = path.spawn;
+        for command in path.commands().iter_mut().take(path.moves.len()) {
+            command.execute(&mut game);
+        }
+        assert_eq!(game.active, target);
+    }
+
+    #[test]
+    fn obstacles_say_what_is_in_the_way() {
+        let model = InputModel::default();
+        let mut board = Board::new(10, 20);
+        // a 2x2 pocket in the corner under a roof
+        board.bulk_add(vec![(0, 2), (1, 2), (2, 0), (2, 1), (2, 2)]);
+
+        let pocket = Placement::new(2, 0, 0, 0);
+        assert!(matches!(
+            reachable(&board, &pocket, &model),
+            Err(Obstacle::Enclosed {
+                closest: Some(_),
+                ..
+            })
+        ));
+        assert_eq!(
+            reachable(&board, &Placement::new(2, 0, 1, 2), &model),
+            Err(Obstacle::Occupied(vec![(1, 2), (2, 2)]))
+        );
+        assert_eq!(
+            reachable(&board, &Placement::new(2, 0, 5, 5), &model),
+            Err(Obstacle::Floating)
+        );
+        assert_eq!(
+            reachable(&board, &Placement::new(2, 0, 0, 9), &model),
+            Err(Obstacle::OutOfBounds)
+        );
+    }
+}
@@ -0,0 +1,186 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::game::Game;
+use crate::piece::Placement;
+use crate::plan::Plan;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::{overlay, resize, FilterType};
+use image::{Delay, Frame, GrayImage, ImageResult, Rgba, RgbaImage};
+use std::fs::File;
+use std::path::Path;
+
+// guideline colours, in piece order Z L O S I J T
+pub const COLOURS: [Rgba<u8>; 7] = [
+    Rgba([227, 50, 56, 255]),
+    Rgba([240, 140, 40, 255]),
+    Rgba([245, 210, 50, 255]),
+    Rgba([90, 200, 70, 255]),
+    Rgba([60, 200, 225, 255]),
+    Rgba([50, 90, 210, 255]),
+    Rgba([170, 70, 200, 255]),
+];
+pub const FILLED: Rgba<u8> = Rgba([150, 150, 150, 255]);
+pub const EMPTY: Rgba<u8> = Rgba([20, 20, 24, 255]);
+const GRID: Rgba<u8> = Rgba([10, 10, 12, 255]);
+
+fn dim(Rgba([r, g, b, a]): Rgba<u8>) -> Rgba<u8> {
+    Rgba([r / 3, g / 3, b / 3, a])
+}
+
+pub fn fill_cell(img: &mut RgbaImage, height: usize, row: usize, col: usize, cell: u32, colour: Rgba<u8>) {
+    let (x0, y0) = (col as u32 * cell, (height - 1 - row) as u32 * cell);
+    for y in 0..cell {
+        for x in 0..cell {
+            // leave a one pixel grid line once cells are big enough to see it
+            let edge = cell >= 4 && (x == cell - 1 || y == cell - 1);
+            img.put_pixel(x0 + x, y0 + y, if edge { GRID } else { colour });
+        }
+    }
+}
+
+fn fill_piece(img: &mut RgbaImage, board: &Board, piece: &Placement, cell: u32, colour: Rgba<u8>) {
+    for [row, col] in piece.abs_locations() {
+        if (0..board.height as i8).contains(&row) && (0..board.width as i8).contains(&col) {
+            fill_cell(img, board.height, row as usize, col as usize, cell, colour);
+        }
+    }
+}
+
+/// Draws `board` at `cell` pixels per cell, with `active` and its ghost on top.
+pub fn render(board: &Board, active: Option<&Placement>, cell: u32) -> RgbaImage {
+    let mut img = RgbaImage::from_pixel(board.width as u32 * cell, board.height as u32 * cell, EMPTY);
+    for row in 0..board.height {
+        for col in 0..board.width {
+            if board.get(row, col) {
+                let colour = board.kind(row, col).map_or(FILLED, |kind| COLOURS[kind]);
+                fill_cell(&mut img, board.height, row, col, cell, colour);
+            }
+        }
+    }
+
+    if let Some(piece) = active.filter(|piece| board.piece_valid_location(piece)) {
+        let colour = COLOURS[piece.piece_type];
+        fill_piece(&mut img, board, &board.ghost(piece), cell, dim(colour));
+        fill_piece(&mut img, board, piece, cell, colour);
+    }
+    img
+}
+
+pub fn render_game(game: &Game, cell: u32) -> RgbaImage {
+    render(&game.board, Some(&game.active), cell)
+}
+
+/// A video frame scaled to the size `render` would draw a `width` by `height` board.
+pub fn render_source(img: &GrayImage, width: usize, height: usize, cell: u32) -> RgbaImage {
+    let rgba = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let v = img.get_pixel(x, y)[0];
+        Rgba([v, v, v, 255])
+    });
+    resize(&rgba, width as u32 * cell, height as u32 * cell, FilterType::Nearest)
+}
+
+/// Places `right` to the right of `left` with a `gap` pixel border between them.
+pub fn beside(left: &RgbaImage, right: &RgbaImage, gap: u32) -> RgbaImage {
+    let mut out = RgbaImage::from_pixel(
+        left.width() + gap + right.width(),
+        left.height().max(right.height()),
+        GRID,
+    );
+    overlay(&mut out, left, 0, 0);
+    overlay(&mut out, right, (left.width() + gap) as i64, 0);
+    out
+}
+
+/// Two frames per step of `plan`, played from `board`: the piece about to
+/// drop, then the board once it has locked.
+pub fn plan_frames(board: &Board, plan: &Plan, cell: u32) -> Vec<RgbaImage> {
+    let mut out = vec![render(board, None, cell)];
+    let mut before = board;
+    for step in plan.steps.iter() {
+        out.push(render(before, Some(&step.placement), cell));
+        out.push(render(&step.board, None, cell));
+        before = &step.board;
+    }
+    out
+}
+
+pub fn write_gif<P: AsRef<Path>>(frames: Vec<RgbaImage>, path: P, delay_ms: u32) -> ImageResult<()> {
+    let mut encoder = GifEncoder::new(File::create(path)?);
+    encoder.set_repeat(Repeat::Infinite)?;
+    encoder.encode_frames(
+        frames
+            .into_iter()
+            .map(|img| Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(delay_ms, 1))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn row_zero_is_drawn_at_the_bottom() {
+        let mut board = Board::new(3, 2);
+        board.paint(0, 0, 6);
+        board.add(1, 2);
+        let img = render(&board, None, 1);
+        assert_eq!(img.dimensions(), (3, 2));
+        assert_eq!(*img.get_pixel(0, 1), COLOURS[6]);
+        assert_eq!(*img.get_pixel(2, 0), FILLED);
+        assert_eq!(*img.get_pixel(0, 0), EMPTY);
+    }
+
+    #[test]
+    fn big_cells_get_a_grid_line() {
+        let mut board = Board::new(1, 1);
+        board.paint(0, 0, 2);
+        let img = render(&board, None, 4);
+        assert_eq!(*img.get_pixel(0, 0), COLOURS[2]);
+        assert_eq!(*img.get_pixel(3, 0), GRID);
+        assert_eq!(*img.get_pixel(0, 3), GRID);
+    }
+
+    #[test]
+    fn active_piece_casts_a_dim_ghost() {
+        let game = Game::new(20, 10, 1);
+        let img = render_game(&game, 1);
+        let kind = game.active.piece_type;
+        let ghost = game.board.ghost(&game.active);
+        for (piece, colour) in [(game.active, COLOURS[kind]), (ghost, dim(COLOURS[kind]))] {
+            for [row, col] in piece.abs_locations() {
+                assert_eq!(*img.get_pixel(col as u32, 19 - row as u32), colour);
+            }
+        }
+    }
+
+    #[test]
+    fn frames_and_gifs() {
+        let left = RgbaImage::new(3, 2);
+        let right = RgbaImage::new(4, 5);
+        assert_eq!(beside(&left, &right, 2).dimensions(), (9, 5));
+
+        let board = Board::new(4, 4);
+        let mut after = board.clone();
+        after.bulk_add(vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+        let plan = Plan {
+            steps: vec![crate::plan::Step {
+                placement: Placement::new(2, 0, 0, 0),
+                inputs: Vec::new(),
+                hold: false,
+                lines_cleared: 0,
+                board: after,
+            }],
+            evaluation: 0,
+        };
+        let frames = plan_frames(&board, &plan, 2);
+        assert_eq!(frames.len(), 3);
+
+        let path = std::env::temp_dir().join(format!("plan-{}.gif", std::process::id()));
+        write_gif(frames, &path, 100).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(bytes.starts_with(b"GIF89a"));
+    }
+}
@@ -0,0 +1,569 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::bot::Bot;
+use crate::control::{Command, Executable};
+use crate::finesse::Key;
+use crate::game::Game;
+use crate::piece::{piece_from_name, piece_name, Placement};
+use crate::text::{board_to_text, ParseError};
+use crate::timing::TimingConfig;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+use std::{fs, io};
+
+// Saved as `key: value` lines, one `lock` line per piece (key index, piece,
+// rotation, row, column, lines cleared), then the starting board and the
+// final board, each with its size:
+//
+//     seed: 1
+//     gravity: 0.02
+//     lock_delay: 30
+//     ...
+//     active: T 0 17 4
+//     hold: I
+//     queue: OSZ
+//     garbage: 3 3
+//     keys: das_left cw hard_drop hold right hard_drop
+//     lock: 2 T 1 0 0 0
+//     lock: 5 I 0 0 4 0
+//     start:
+//     width: 10
+//     height: 20
+//     ...
+//     board:
+//     width: 10
+//     height: 20
+//     ...
+
+/// Where a piece locked, and after which key.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    pub key: usize,
+    pub placement: Placement,
+    pub lines_cleared: usize,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// the history holds a command no player could input, such as `SetPiece`
+    NotAnInput(usize),
+    UnknownKey(String),
+    BadValue(String),
+    Board(ParseError),
+    Missing(&'static str),
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Divergence {
+    /// piece `piece` locked differently, or only one side locked it at all
+    Lock {
+        piece: usize,
+        expected: Option<Checkpoint>,
+        actual: Option<Checkpoint>,
+    },
+    /// every piece matched but these (row, col) cells of the final board did not
+    Board { cells: Vec<(usize, usize)> },
+    /// played back under rules that differ from the recorded ones in these
+    /// settings
+    Rules(Vec<&'static str>),
+}
+
+/// Everything needed to play a game again from the start: the starting
+/// board, pieces and pending garbage, the rules and the keys pressed, plus
+/// where each piece locked and the final board so a playback can be checked.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Replay {
+    /// queue generator state at the start, past the pieces in `queue`
+    pub seed: usize,
+    pub start: Board,
+    pub active: Placement,
+    pub hold: Option<usize>,
+    pub queue: Vec<usize>,
+    /// hole columns of the garbage pending at the start, next first
+    pub garbage: Vec<usize>,
+    /// the real-time rules the game was played under
+    pub rules: TimingConfig,
+    pub keys: Vec<Key>,
+    pub checkpoints: Vec<Checkpoint>,
+    pub board: Board,
+}
+
+fn record_command(
+    command: &Command,
+    keys: &mut Vec<Key>,
+    checkpoints: &mut Vec<Checkpoint>,
+) -> bool {
+    match command {
+        Command::Batch(batch) => batch
+            .commands
+            .iter()
+            .all(|command| record_command(command, keys, checkpoints)),
+        Command::PlacementActions(actions) => actions
+            .batch
+            .commands
+            .iter()
+            .all(|command| record_command(command, keys, checkpoints)),
+        command => match Key::from_command(command) {
+            Some(pressed) => {
+                keys.extend(pressed);
+                if let Command::HardDrop(drop) = command {
+                    checkpoints.push(Checkpoint {
+                        key: keys.len() - 1,
+                        placement: drop.placement().unwrap(),
+                        lines_cleared: drop.lines_cleared(),
+                    });
+                }
+                true
+            }
+            None => false,
+        },
+    }
+}
+
+impl Replay {
+    /// Records everything on the bot's undo stack, starting from the game as
+    /// it was before the oldest command on it, as played under `rules`.
+    pub fn record(bot: &Bot, rules: TimingConfig) -> Result<Self, ReplayError> {
+        let history: Vec<Command> = bot.history().cloned().collect();
+        let mut keys = Vec::new();
+        let mut checkpoints = Vec::new();
+        for (index, command) in history.iter().enumerate() {
+            if !record_command(command, &mut keys, &mut checkpoints) {
+                return Err(ReplayError::NotAnInput(index));
+            }
+        }
+
+        let mut start = bot.game.clone();
+        for mut command in history.into_iter().rev() {
+            command.undo(&mut start);
+        }
+        Ok(Self {
+            seed: start.queue.state(),
+            start: start.board,
+            active: start.active,
+            hold: start.hold,
+            queue: start.queue.pieces.into_iter().collect(),
+            garbage: start.pending_garbage.into_iter().collect(),
+            rules,
+            keys,
+            checkpoints,
+            board: bot.game.board.clone(),
+        })
+    }
+
+    /// The game as it was when recording started.
+    pub fn start(&self) -> Game {
+        let mut game = Game::new(self.start.height, self.start.width, self.seed);
+        game.board = self.start.clone();
+        game.active = self.active;
+        game.hold = self.hold;
+        game.queue.pieces = self.queue.iter().copied().collect();
+        game.pending_garbage = self.garbage.iter().copied().collect();
+        game
+    }
+
+    /// Presses every key on the starting game.
+    pub fn play(&self) -> (Game, Vec<Checkpoint>) {
+        let mut game = self.start();
+        let mut checkpoints = Vec::new();
+        for (index, key) in self.keys.iter().enumerate() {
+            let mut command = key.command();
+            command.execute(&mut game);
+            if let Command::HardDrop(drop) = &command {
+                checkpoints.push(Checkpoint {
+                    key: index,
+                    placement: drop.placement().unwrap(),
+                    lines_cleared: drop.lines_cleared(),
+                });
+            }
+        }
+        (game, checkpoints)
+    }
+
+    /// Plays the replay under `rules` and reports the first piece that
+    /// locked somewhere other than recorded. Rules other than the recorded
+    /// ones are refused before anything is played.
+    pub fn verify(&self, rules: &TimingConfig) -> Result<Game, Divergence> {
+        let differ = rule_differences(&self.rules, rules);
+        if !differ.is_empty() {
+            return Err(Divergence::Rules(differ));
+        }
+        let (game, actual) = self.play();
+        let pieces = self.checkpoints.len().max(actual.len());
+        for piece in 0..pieces {
+            let (expected, actual) = (self.checkpoints.get(piece), actual.get(piece));
+            // the key index is only there to say where, an extra key that
+            // changes nothing is not a divergence
+            let same = match (expected, actual) {
+                (Some(expected), Some(actual)) => {
+                    (expected.placement, expected.lines_cleared)
+                        == (actual.placement, actual.lines_cleared)
+                }
+                _ => false,
+            };
+            if !same {
+                return Err(Divergence::Lock {
+                    piece,
+                    expected: expected.copied(),
+                    actual: actual.copied(),
+                });
+            }
+        }
+        let (width, height) = (self.board.width, self.board.height);
+        let cells: Vec<(usize, usize)> = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .filter(|&(row, col)| {
+                game.board.get(row, col) != self.board.get(row, col)
+                    || game.board.kind(row, col) != self.board.kind(row, col)
+            })
+            .collect();
+        if !cells.is_empty() {
+            return Err(Divergence::Board { cells });
+        }
+        Ok(game)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ReplayError> {
+        fs::read_to_string(path)?.parse()
+    }
+}
+
+/// Every rule as its name in the text format and its value.
+fn rule_values(rules: &TimingConfig) -> [(&'static str, f64); 8] {
+    [
+        ("gravity", rules.gravity as f64),
+        ("lock_delay", rules.lock_delay as f64),
+        ("move_resets", rules.move_resets as f64),
+        ("are", rules.are as f64),
+        ("line_clear_delay", rules.line_clear_delay as f64),
+        ("das", rules.das as f64),
+        ("arr", rules.arr as f64),
+        ("soft_drop_factor", rules.soft_drop_factor as f64),
+    ]
+}
+
+fn rule_differences(recorded: &TimingConfig, playback: &TimingConfig) -> Vec<&'static str> {
+    rule_values(recorded)
+        .into_iter()
+        .zip(rule_values(playback))
+        .filter(|((_, a), (_, b))| a != b)
+        .map(|((name, _), _)| name)
+        .collect()
+}
+
+fn key_name(key: Key) -> String {
+    match key {
+        Key::Left => "left".to_string(),
+        Key::Right => "right".to_string(),
+        Key::DasLeft => "das_left".to_string(),
+        Key::DasRight => "das_right".to_string(),
+        Key::Cw => "cw".to_string(),
+        Key::Ccw => "ccw".to_string(),
+        Key::Flip => "flip".to_string(),
+        Key::SoftDrop => "soft_drop".to_string(),
+        Key::SoftDropBy(rows) => format!("soft_drop:{}", rows),
+        Key::SonicDrop => "sonic_drop".to_string(),
+        Key::HardDrop => "hard_drop".to_string(),
+        Key::Hold => "hold".to_string(),
+    }
+}
+
+fn parse_key(name: &str) -> Result<Key, ReplayError> {
+    Ok(match name {
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "das_left" => Key::DasLeft,
+        "das_right" => Key::DasRight,
+        "cw" => Key::Cw,
+        "ccw" => Key::Ccw,
+        "flip" => Key::Flip,
+        "soft_drop" => Key::SoftDrop,
+        "sonic_drop" => Key::SonicDrop,
+        "hard_drop" => Key::HardDrop,
+        "hold" => Key::Hold,
+        name => match name.strip_prefix("soft_drop:") {
+            Some(rows) => Key::SoftDropBy(value(rows)?),
+            None => return Err(ReplayError::UnknownKey(name.to_string())),
+        },
+    })
+}
+
+fn value<T: FromStr>(text: &str) -> Result<T, ReplayError> {
+    text.parse()
+        .map_err(|_| ReplayError::BadValue(text.to_string()))
+}
+
+fn parse_piece(name: &str) -> Result<usize, ReplayError> {
+    let mut chars = name.chars();
+    match (chars.next().and_then(piece_from_name), chars.next()) {
+        (Some(piece_type), None) => Ok(piece_type),
+        _ => Err(ReplayError::BadValue(name.to_string())),
+    }
+}
+
+fn parse_placement(fields: &[&str]) -> Result<Placement, ReplayError> {
+    let [piece, rotation, row, col] = fields[..] else {
+        return Err(ReplayError::BadValue(fields.join(" ")));
+    };
+    Ok(Placement::new(
+        parse_piece(piece)?,
+        value(rotation)?,
+        value(row)?,
+        value(col)?,
+    ))
+}
+
+fn parse_checkpoint(text: &str) -> Result<Checkpoint, ReplayError> {
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    let [key, ref placement @ .., lines] = fields[..] else {
+        return Err(ReplayError::BadValue(text.to_string()));
+    };
+    Ok(Checkpoint {
+        key: value(key)?,
+        placement: parse_placement(placement)?,
+        lines_cleared: value(lines)?,
+    })
+}
+
+fn placement_text(placement: &Placement) -> String {
+    format!(
+        "{} {} {} {}",
+        piece_name(placement.piece_type),
+        placement.rotation_state,
+        placement.row,
+        placement.col
+    )
+}
+
+impl Display for Replay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "seed: {}", self.seed)?;
+        let rules = &self.rules;
+        writeln!(f, "gravity: {}", rules.gravity)?;
+        writeln!(f, "lock_delay: {}", rules.lock_delay)?;
+        writeln!(f, "move_resets: {}", rules.move_resets)?;
+        writeln!(f, "are: {}", rules.are)?;
+        writeln!(f, "line_clear_delay: {}", rules.line_clear_delay)?;
+        writeln!(f, "das: {}", rules.das)?;
+        writeln!(f, "arr: {}", rules.arr)?;
+        writeln!(f, "soft_drop_factor: {}", rules.soft_drop_factor)?;
+        writeln!(f, "active: {}", placement_text(&self.active))?;
+        if let Some(hold) = self.hold {
+            writeln!(f, "hold: {}", piece_name(hold))?;
+        }
+        let queue: String = self.queue.iter().map(|&piece| piece_name(piece)).collect();
+        writeln!(f, "queue: {}", queue)?;
+        let garbage: Vec<String> = self.garbage.iter().map(usize::to_string).collect();
+        writeln!(f, "garbage: {}", garbage.join(" "))?;
+        let keys: Vec<String> = self.keys.iter().map(|&key| key_name(key)).collect();
+        writeln!(f, "keys: {}", keys.join(" "))?;
+        for checkpoint in self.checkpoints.iter() {
+            writeln!(
+                f,
+                "lock: {} {} {}",
+                checkpoint.key,
+                placement_text(&checkpoint.placement),
+                checkpoint.lines_cleared
+            )?;
+        }
+        writeln!(f, "start:")?;
+        write!(f, "{}", board_to_text(&self.start))?;
+        writeln!(f, "board:")?;
+        write!(f, "{}", board_to_text(&self.board))
+    }
+}
+
+impl FromStr for Replay {
+    type Err = ReplayError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (header, boards) = text
+            .split_once("start:")
+            .ok_or(ReplayError::Missing("start"))?;
+        let (start, board) = boards
+            .split_once("board:")
+            .ok_or(ReplayError::Missing("board"))?;
+
+        let (mut seed, mut active) = (None, None);
+        let mut rules = TimingConfig::default();
+        let (mut hold, mut queue, mut garbage) = (None, Vec::new(), Vec::new());
+        let mut keys = Vec::new();
+        let mut checkpoints = Vec::new();
+        for line in header
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let Some((key, text)) = line.split_once(':') else {
+                return Err(ReplayError::BadValue(line.to_string()));
+            };
+            let text = text.trim();
+            match key.trim() {
+                "seed" => seed = Some(value(text)?),
+                "gravity" => rules.gravity = value(text)?,
+                "lock_delay" => rules.lock_delay = value(text)?,
+                "move_resets" => rules.move_resets = value(text)?,
+                "are" => rules.are = value(text)?,
+                "line_clear_delay" => rules.line_clear_delay = value(text)?,
+                "das" => rules.das = value(text)?,
+                "arr" => rules.arr = value(text)?,
+                "soft_drop_factor" => rules.soft_drop_factor = value(text)?,
+                "active" => {
+                    let fields: Vec<&str> = text.split_whitespace().collect();
+                    active = Some(parse_placement(&fields)?);
+                }
+                "hold" => hold = Some(parse_piece(text)?),
+                "queue" => {
+                    queue = text
+                        .chars()
+                        .map(|c| parse_piece(&c.to_string()))
+                        .collect::<Result<_, _>>()?;
+                }
+                "garbage" => {
+                    garbage = text
+                        .split_whitespace()
+                        .map(value)
+                        .collect::<Result<_, _>>()?;
+                }
+                "keys" => {
+                    keys = text
+                        .split_whitespace()
+                        .map(parse_key)
+                        .collect::<Result<_, _>>()?;
+                }
+                "lock" => checkpoints.push(parse_checkpoint(text)?),
+                key => return Err(ReplayError::UnknownKey(key.to_string())),
+            }
+        }
+
+        Ok(Self {
+            seed: seed.ok_or(ReplayError::Missing("seed"))?,
+            start: start.parse().map_err(ReplayError::Board)?,
+            active: active.ok_or(ReplayError::Missing("active"))?,
+            hold,
+            queue,
+            garbage,
+            rules,
+            keys,
+            checkpoints,
+            board: board.parse().map_err(ReplayError::Board)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bot that starts mid-game: a dirty board, a held piece and garbage
+    /// on the way, then plays a few pieces by hand.
+    fn played() -> Bot {
+        let mut bot = Bot::new(20, 10, 3);
+        bot.game
+            .board
+            .bulk_add(vec![(0, 0), (0, 1), (0, 2), (1, 0)]);
+        bot.game.board.paint(0, 9, 4);
+        bot.game.hold = Some(6);
+        bot.game.pending_garbage.extend([3, 3]);
+
+        bot.das_left();
+        bot.hard_drop();
+        bot.hold();
+        bot.rotate_cw();
+        bot.move_right();
+        bot.hard_drop();
+        bot.soft_drop();
+        bot.hard_drop();
+        bot
+    }
+
+    #[test]
+    fn records_the_starting_game() {
+        let bot = played();
+        let replay = Replay::record(&bot, TimingConfig::default()).unwrap();
+        assert_eq!(replay.hold, Some(6));
+        assert_eq!(replay.garbage, vec![3, 3]);
+        assert!(replay.start.get(1, 0));
+        assert_eq!(replay.checkpoints.len(), 3);
+        assert_eq!(
+            replay.verify(&TimingConfig::default()).unwrap().board,
+            bot.game.board
+        );
+    }
+
+    #[test]
+    fn save_load_verify() {
+        let replay = Replay::record(&played(), TimingConfig::default()).unwrap();
+        let path = std::env::temp_dir().join(format!("replay-{}.txt", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.to_string(), replay.to_string());
+        assert_eq!(loaded.checkpoints, replay.checkpoints);
+        assert!(loaded.verify(&TimingConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn engine_commands_are_not_inputs() {
+        let mut bot = Bot::new(20, 10, 3);
+        bot.hard_drop();
+        bot.add_garbage(vec![0]);
+        assert!(matches!(
+            Replay::record(&bot, TimingConfig::default()),
+            Err(ReplayError::NotAnInput(1))
+        ));
+    }
+
+    #[test]
+    fn a_changed_key_diverges() {
+        let mut replay = Replay::record(&played(), TimingConfig::default()).unwrap();
+        replay.keys[0] = Key::DasRight;
+        assert!(matches!(
+            replay.verify(&TimingConfig::default()),
+            Err(Divergence::Lock { piece: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn other_rules_are_refused() {
+        let fast = TimingConfig {
+            gravity: 1.0,
+            das: 8,
+            ..TimingConfig::default()
+        };
+        let replay = Replay::record(&played(), fast).unwrap();
+        let loaded: Replay = replay.to_string().parse().unwrap();
+        assert_eq!(loaded.rules, fast);
+        assert!(loaded.verify(&fast).is_ok());
+        assert_eq!(
+            loaded.verify(&TimingConfig::default()).err(),
+            Some(Divergence::Rules(vec!["gravity", "das"]))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let replay = Replay::record(&played(), TimingConfig::default()).unwrap();
+        let json = serde_json::to_string(&replay).unwrap();
+        let loaded: Replay = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.to_string(), replay.to_string());
+        assert!(loaded.verify(&TimingConfig::default()).is_ok());
+    }
+}
@@ -0,0 +1,493 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::control::Hold;
+use crate::finesse::InputModel;
+use crate::game::Game;
+use crate::piece::{Placement, Point};
+use crate::plan::{Plan, Step};
+use crate::reachability::{reachable, Obstacle, Path};
+use std::collections::{HashMap, HashSet};
+
+/// Most filler spots tried for one piece before giving up on burning it.
+const FILLER_CHOICES: usize = 3;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ScheduleConfig {
+    pub model: InputModel,
+    /// pieces that may be dropped off the plan, out of the way, when neither
+    /// the active piece nor hold fits anywhere; rows they finish clear when
+    /// nothing but fillers lies above, the rest are left for the caller
+    pub max_fillers: usize,
+}
+
+/// Why a placement couldn't go in when the search got stuck.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Reason {
+    /// a fresh piece can't lock there yet
+    Unreachable(Obstacle),
+    /// it could go in, but neither the active piece nor hold is its type
+    NotInQueue,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Blocker {
+    /// planned pieces placed before getting stuck
+    pub placed: usize,
+    /// the lowest planned piece still to place
+    pub placement: Placement,
+    pub reason: Reason,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScheduleError {
+    /// two placements share a cell
+    Overlap(Placement, Placement),
+    /// a full row would clear before the last piece is in
+    FullRow(usize),
+    /// the furthest any order got
+    Blocked(Blocker),
+}
+
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    pub plan: Plan,
+    /// indices into `plan.steps` of filler pieces
+    pub fillers: Vec<usize>,
+}
+
+fn full_row(board: &Board) -> Option<usize> {
+    (0..board.height).find(|&row| (0..board.width).all(|col| board.get(row, col)))
+}
+
+/// The board, which planned pieces are placed, hold and the next piece.
+type State = (Vec<Vec<bool>>, Vec<bool>, Option<usize>, usize);
+
+struct Search<'a> {
+    placements: &'a [Placement],
+    /// the active piece, then the queue
+    pieces: &'a [usize],
+    config: ScheduleConfig,
+    board: Board,
+    placed: Vec<bool>,
+    steps: Vec<Step>,
+    fillers: Vec<usize>,
+    /// cells of `board` filled by fillers
+    loose: Vec<Vec<bool>>,
+    /// states that lead nowhere
+    dead: HashSet<State>,
+    blocker: Option<Blocker>,
+}
+
+impl Search<'_> {
+    fn reach(&self, target: &Placement) -> Result<Path, Obstacle> {
+        reachable(&self.board, target, &self.config.model)
+    }
+
+    /// Clears the full rows, which only works when none of them holds a
+    /// planned cell and every cell above them that stays is a filler's, so
+    /// nothing planned moves. Returns the rows cleared, or None if a full
+    /// row has to stay.
+    fn clear_fillers(&mut self) -> Option<usize> {
+        let full: Vec<usize> = (0..self.board.height)
+            .filter(|&row| (0..self.board.width).all(|col| self.board.get(row, col)))
+            .collect();
+        let Some(&lowest) = full.first() else {
+            return Some(0);
+        };
+        let planned: HashSet<Point> = (0..self.placements.len())
+            .filter(|&i| self.placed[i])
+            .flat_map(|i| self.placements[i].abs_locations())
+            .collect();
+        if planned
+            .iter()
+            .any(|&[row, _]| full.contains(&(row as usize)))
+        {
+            return None;
+        }
+        for row in lowest..self.board.height {
+            if full.contains(&row) {
+                continue;
+            }
+            if (0..self.board.width).any(|col| self.board.get(row, col) && !self.loose[row][col]) {
+                return None;
+            }
+        }
+        for &row in full.iter().rev() {
+            self.board.line_clear(row);
+            self.loose.remove(row);
+            self.loose.push(vec![false; self.board.width]);
+        }
+        Some(full.len())
+    }
+
+    fn descend(
+        &mut self,
+        target: Placement,
+        hold: bool,
+        filler: bool,
+        next: usize,
+        held: Option<usize>,
+    ) -> bool {
+        let Ok(path) = self.reach(&target) else {
+            return false;
+        };
+        let mut inputs = path.commands();
+        if hold {
+            inputs.insert(0, Hold::new().into());
+        }
+        let before = (self.board.clone(), self.loose.clone());
+        for [row, col] in target.abs_locations() {
+            self.board
+                .paint(row as usize, col as usize, target.piece_type);
+            self.loose[row as usize][col as usize] = filler;
+        }
+        let Some(lines_cleared) = self.clear_fillers() else {
+            (self.board, self.loose) = before;
+            return false;
+        };
+
+        if filler {
+            self.fillers.push(self.steps.len());
+        }
+        self.steps.push(Step {
+            placement: path.placement,
+            inputs,
+            hold,
+            lines_cleared,
+            board: self.board.clone(),
+        });
+        if self.order(next, held) {
+            return true;
+        }
+        self.steps.pop();
+        if filler {
+            self.fillers.pop();
+        }
+        (self.board, self.loose) = before;
+        false
+    }
+
+    fn place(&mut self, piece: usize, hold: bool, next: usize, held: Option<usize>) -> bool {
+        for i in 0..self.placements.len() {
+            let target = self.placements[i];
+            if self.placed[i]
+                || target.piece_type != piece
+                || !self.board.piece_valid_placement(&target)
+            {
+                continue;
+            }
+            self.placed[i] = true;
+            if self.descend(target, hold, false, next, held) {
+                return true;
+            }
+            self.placed[i] = false;
+        }
+        false
+    }
+
+    /// Spots for `piece` clear of every planned cell still open, those
+    /// covering the fewest of them from above first, then those leaving the
+    /// fewest cells behind after the rows they finish clear, then the lowest.
+    fn filler_spots(&self, piece: usize) -> Vec<Placement> {
+        let open: HashSet<Point> = (0..self.placements.len())
+            .filter(|&i| !self.placed[i])
+            .flat_map(|i| self.placements[i].abs_locations())
+            .collect();
+        let covers = |cells: &[Point; 4]| {
+            cells
+                .iter()
+                .filter(|[row, col]| open.iter().any(|[r, c]| c == col && r < row))
+                .count()
+        };
+
+        let mut seen = HashSet::new();
+        let mut out = Vec::new();
+        for rotation in 0..4 {
+            for row in -2..self.board.height as i8 + 2 {
+                for col in -2..self.board.width as i8 + 2 {
+                    let spot = Placement::new(piece, rotation, row, col);
+                    if !self.board.piece_valid_placement(&spot) {
+                        continue;
+                    }
+                    let cells = spot.abs_locations();
+                    if cells.iter().all(|cell| !open.contains(cell)) && seen.insert(cells) {
+                        out.push(spot);
+                    }
+                }
+            }
+        }
+        let left = |cells: &[Point; 4]| {
+            cells
+                .iter()
+                .filter(|[row, _]| {
+                    let filled = (0..self.board.width)
+                        .filter(|&col| self.board.get(*row as usize, col))
+                        .count();
+                    let added = cells.iter().filter(|[r, _]| r == row).count();
+                    filled + added < self.board.width
+                })
+                .count()
+        };
+        out.sort_by_key(|spot| {
+            let cells = spot.abs_locations();
+            (covers(&cells), left(&cells), cells[3][0])
+        });
+        out
+    }
+
+    fn burn(&mut self, piece: usize, next: usize, hold: Option<usize>) -> bool {
+        if self.fillers.len() >= self.config.max_fillers {
+            return false;
+        }
+        let mut tried = 0;
+        for spot in self.filler_spots(piece) {
+            if tried == FILLER_CHOICES {
+                break;
+            }
+            if self.reach(&spot).is_err() {
+                continue;
+            }
+            tried += 1;
+            if self.descend(spot, false, true, next + 1, hold) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The lowest planned piece still to place and why it can't go in now.
+    /// Only asked where no planned piece fits, so a placement that could go
+    /// in is waiting on its piece.
+    fn stuck(&self) -> Option<Blocker> {
+        let lowest = (0..self.placements.len())
+            .filter(|&i| !self.placed[i])
+            .map(|i| self.placements[i])
+            .min_by_key(|placement| placement.abs_locations()[0])?;
+        let reason = match self.reach(&lowest) {
+            Err(obstacle) => Reason::Unreachable(obstacle),
+            Ok(_) => Reason::NotInQueue,
+        };
+        Some(Blocker {
+            placed: self.placed.iter().filter(|&&placed| placed).count(),
+            placement: lowest,
+            reason,
+        })
+    }
+
+    fn order(&mut self, next: usize, hold: Option<usize>) -> bool {
+        if self.placed.iter().all(|&placed| placed) {
+            return true;
+        }
+        let state = (self.board.arr.clone(), self.placed.clone(), hold, next);
+        if self.dead.contains(&state) {
+            return false;
+        }
+
+        let found = match self.pieces.get(next) {
+            None => false,
+            Some(&active) => {
+                self.place(active, false, next + 1, hold)
+                    || match hold {
+                        Some(held) if held != active => {
+                            self.place(held, true, next + 1, Some(active))
+                        }
+                        Some(_) => false,
+                        None => match self.pieces.get(next + 1) {
+                            Some(&swapped) => self.place(swapped, true, next + 2, Some(active)),
+                            None => false,
+                        },
+                    }
+                    || self.burn(active, next, hold)
+            }
+        };
+        if !found {
+            let placed = self.placed.iter().filter(|&&placed| placed).count();
+            if self
+                .blocker
+                .as_ref()
+                .is_none_or(|blocker| placed > blocker.placed)
+            {
+                self.blocker = self.stuck();
+            }
+            self.dead.insert(state);
+        }
+        found
+    }
+}
+
+/// Finds an order to lock `placements` in from `game`: each piece comes
+/// from the queue, or from hold, rests on the floor or on something placed
+/// before it, and can be reached from spawn. With `config.max_fillers` set,
+/// pieces nothing can use may be dropped out of the way instead, and only
+/// those clear lines.
+pub fn schedule(
+    game: &Game,
+    placements: &[Placement],
+    config: &ScheduleConfig,
+) -> Result<Schedule, ScheduleError> {
+    let mut owner = HashMap::new();
+    let mut after = game.board.clone();
+    for placement in placements {
+        for cell in placement.abs_locations() {
+            if let Some(other) = owner.insert(cell, *placement) {
+                return Err(ScheduleError::Overlap(other, *placement));
+            }
+            let [row, col] = cell;
+            if row >= 0 && col >= 0 && (row as usize) < after.height && (col as usize) < after.width
+            {
+                after.add(row as usize, col as usize);
+            }
+        }
+    }
+    if let Some(row) = full_row(&after) {
+        return Err(ScheduleError::FullRow(row));
+    }
+
+    // every planned piece, every filler and one more for hold
+    let mut queue = game.queue.clone();
+    let pieces: Vec<usize> = std::iter::once(game.active.piece_type)
+        .chain((0..placements.len() + config.max_fillers).map(|_| queue.next()))
+        .collect();
+
+    // bottom up is the likeliest order to work
+    let mut sorted = placements.to_vec();
+    sorted.sort_by_key(|placement| placement.abs_locations()[0]);
+    let mut search = Search {
+        placements: &sorted,
+        pieces: &pieces,
+        config: *config,
+        board: game.board.clone(),
+        placed: vec![false; sorted.len()],
+        steps: Vec::new(),
+        fillers: Vec::new(),
+        loose: vec![vec![false; game.board.width]; game.board.height],
+        dead: HashSet::new(),
+        blocker: None,
+    };
+    if search.order(0, game.hold) {
+        Ok(Schedule {
+            plan: Plan {
+                steps: search.steps,
+                evaluation: 0,
+            },
+            fillers: search.fillers,
+        })
+    } else {
+        Err(ScheduleError::Blocked(search.blocker.unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::piece_from_name;
+
+    /// An empty game with `queue` dealt first.
+    fn dealt(queue: &str) -> Game {
+        let mut game = Game::new(20, 10, 1);
+        let mut pieces = queue.chars().map(|c| piece_from_name(c).unwrap());
+        game.active = game.new_piece(pieces.next().unwrap());
+        game.queue.pieces = pieces.chain(game.queue.pieces.iter().copied()).collect();
+        game
+    }
+
+    #[test]
+    fn builds_from_the_bottom_up() {
+        let top = Placement::new(2, 0, 2, 0);
+        let bottom = Placement::new(2, 0, 0, 0);
+        let out = schedule(&dealt("OO"), &[top, bottom], &ScheduleConfig::default()).unwrap();
+        let placed: Vec<Placement> = out.plan.steps.iter().map(|step| step.placement).collect();
+        assert_eq!(placed, vec![bottom, top]);
+        assert!(out.fillers.is_empty());
+        assert!(out.plan.steps.iter().all(|step| !step.hold));
+        assert!(out.plan.steps[1].board.get(3, 1));
+    }
+
+    #[test]
+    fn overlaps_and_full_rows_are_refused() {
+        let config = ScheduleConfig::default();
+        let one = Placement::new(2, 0, 0, 0);
+        let other = Placement::new(2, 0, 1, 1);
+        assert_eq!(
+            schedule(&dealt("OO"), &[one, other], &config).unwrap_err(),
+            ScheduleError::Overlap(one, other)
+        );
+
+        // two flat I pieces and an O close the bottom two rows
+        let mut game = dealt("IIO");
+        game.board.bulk_add((0..8).map(|col| (1, col)).collect());
+        let row = [
+            Placement::new(4, 0, 0, 1),
+            Placement::new(4, 0, 0, 5),
+            Placement::new(2, 0, 0, 8),
+        ];
+        assert_eq!(
+            schedule(&game, &row, &config).unwrap_err(),
+            ScheduleError::FullRow(0)
+        );
+    }
+
+    #[test]
+    fn hold_brings_the_next_piece_forward() {
+        let target = Placement::new(2, 0, 0, 0);
+        let out = schedule(&dealt("TO"), &[target], &ScheduleConfig::default()).unwrap();
+        let step = &out.plan.steps[0];
+        assert_eq!(step.placement, target);
+        assert!(step.hold);
+        assert_eq!(step.inputs[0], Hold::new().into());
+    }
+
+    #[test]
+    fn blockers_say_why() {
+        let config = ScheduleConfig::default();
+        let target = Placement::new(2, 0, 0, 0);
+        let blocked =
+            |game: &Game, placements: &[Placement]| match schedule(game, placements, &config)
+                .unwrap_err()
+            {
+                ScheduleError::Blocked(blocker) => blocker,
+                other => panic!("{other:?}"),
+            };
+
+        let blocker = blocked(&dealt("TTT"), &[target]);
+        assert_eq!((blocker.placed, blocker.reason), (0, Reason::NotInQueue));
+
+        // the second O has nothing under it until the first is in
+        let above = Placement::new(2, 0, 2, 4);
+        let blocker = blocked(&dealt("OTT"), &[target, above]);
+        assert_eq!(blocker.placed, 1);
+        assert_eq!(blocker.placement, above);
+        assert_eq!(blocker.reason, Reason::Unreachable(Obstacle::Floating));
+    }
+
+    #[test]
+    fn fillers_clear_the_rows_they_finish() {
+        // the O goes where the bottom row is, so that row has to go first
+        let mut game = dealt("IO");
+        game.board.bulk_add((0..9).map(|col| (0, col)).collect());
+        let target = Placement::new(2, 0, 0, 0);
+
+        let blocked = schedule(&game, &[target], &ScheduleConfig::default()).unwrap_err();
+        let occupied = Reason::Unreachable(Obstacle::Occupied(vec![(0, 0), (0, 1)]));
+        assert!(
+            matches!(blocked, ScheduleError::Blocked(Blocker { reason, .. }) if reason == occupied)
+        );
+
+        let config = ScheduleConfig {
+            max_fillers: 1,
+            ..ScheduleConfig::default()
+        };
+        let out = schedule(&game, &[target], &config).unwrap();
+        assert_eq!(out.fillers, vec![0]);
+        let [filler, step] = &out.plan.steps[..] else {
+            panic!("{:?}", out.plan.steps);
+        };
+        assert_eq!(filler.lines_cleared, 1);
+        assert_eq!(step.placement, target);
+        // what's left of the I stands in the last column
+        let board = &step.board;
+        assert!(board.get(0, 0) && board.get(1, 1) && board.get(2, 9));
+        assert!(!board.get(0, 2) && !board.get(3, 9));
+    }
+}
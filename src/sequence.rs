@@ -0,0 +1,290 @@
+#![allow(dead_code)]
+
+use crate::analyzer::frame_diff;
+use crate::board::Board;
+use crate::bot::{score, score_in, Bot};
+use crate::diff::{compare, Comparison};
+use crate::game::Game;
+use crate::plan::Step;
+use crate::viewport::Viewport;
+
+#[derive(Copy, Clone, Debug)]
+pub struct SequenceConfig {
+    pub depth: usize,
+    pub candidates: usize,
+    /// most pieces spent moving from one frame to the next
+    pub max_pieces: usize,
+    /// frames changing fewer than this fraction of cells are left as they are
+    pub skip_below: f32,
+    /// frames changing fewer than this fraction of cells only get new pieces
+    /// added, without clearing lines to take the outdated ones away
+    pub partial_below: f32,
+    /// paint frames into this part of a taller playfield, scoring pieces
+    /// only by the cells they put inside it
+    pub viewport: Option<Viewport>,
+}
+
+/// How much work a frame got, decided by how far it is from the last frame
+/// that was actually planned.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Transition {
+    Skip,
+    Partial,
+    Full,
+}
+
+impl Default for SequenceConfig {
+    fn default() -> Self {
+        Self {
+            depth: 1,
+            candidates: 10,
+            max_pieces: 200,
+            skip_below: 0.005,
+            partial_below: 0.02,
+            viewport: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FrameReport {
+    pub frame: usize,
+    pub transition: Transition,
+    /// index into `Sequencer::steps` of this frame's first placement
+    pub first_step: usize,
+    pub pieces: usize,
+    pub lines_cleared: usize,
+    pub comparison: Comparison,
+}
+
+/// The lowest row holding a cell that is painted but should be empty.
+fn lowest_wrong_row(painted: &Board, target: &Board) -> Option<usize> {
+    (0..painted.height)
+        .find(|&row| (0..painted.width).any(|col| painted.get(row, col) && !target.get(row, col)))
+}
+
+fn highest_filled_row(board: &Board) -> Option<usize> {
+    (0..board.height)
+        .rev()
+        .find(|&row| (0..board.width).any(|col| board.get(row, col)))
+}
+
+/// Clearing a row drops everything above it, so to get rid of a wrong cell
+/// every row from there to the top of the stack has to go. Asks for those
+/// rows to be filled, nothing above them, and the target below them.
+fn wipe_target(target: &Board, bottom: usize, top: usize) -> Board {
+    let mut out = Board::new(target.width, target.height);
+    for row in 0..target.height {
+        for col in 0..target.width {
+            if row >= bottom && row <= top || row < bottom && target.get(row, col) {
+                out.add(row, col);
+            }
+        }
+    }
+    out
+}
+
+type Observer = Box<dyn FnMut(&Game, &Board)>;
+
+/// Paints a run of frames as one continuous game. Each frame first clears
+/// lines until nothing outdated is left (or no piece helps any more), then
+/// places pieces towards the new target for as long as that helps.
+pub struct Sequencer {
+    pub bot: Bot,
+    pub config: SequenceConfig,
+    pub steps: Vec<Step>,
+    pub reports: Vec<FrameReport>,
+    /// the last target that was planned towards, which skipped frames are
+    /// compared against so small changes can't pile up unnoticed
+    planned: Option<Board>,
+    observer: Option<Observer>,
+}
+
+impl Sequencer {
+    pub fn new(bot: Bot, config: SequenceConfig) -> Self {
+        Self {
+            bot,
+            config,
+            steps: Vec::new(),
+            reports: Vec::new(),
+            planned: None,
+            observer: None,
+        }
+    }
+
+    /// Calls `observer` with the game and the frame being painted after
+    /// every placement.
+    pub fn observe(&mut self, observer: impl FnMut(&Game, &Board) + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    /// Plays the best next piece towards `goal` if it scores better than
+    /// leaving the board alone, counting only cells inside `viewport` if
+    /// there is one. `target` is the frame being painted, which is what the
+    /// observer gets to see.
+    fn place(&mut self, goal: &Board, target: &Board, viewport: Option<Viewport>) -> Option<&Step> {
+        let (depth, candidates) = (self.config.depth, self.config.candidates);
+        let plan = match viewport {
+            Some(viewport) => self.bot.best_plan_in(depth, candidates, goal, &viewport)?,
+            None => self.bot.best_plan(depth, candidates, goal)?,
+        };
+        let step = plan.steps.into_iter().next()?;
+        let gain = match viewport {
+            Some(viewport) => score_in(&vec![step.placement], goal, &viewport),
+            None => score(&vec![step.placement], goal),
+        };
+        if gain >= 0 {
+            return None;
+        }
+        self.bot.play(&step);
+        if let Some(observer) = self.observer.as_mut() {
+            observer(&self.bot.game, target);
+        }
+        self.steps.push(step);
+        self.steps.last()
+    }
+
+    fn transition(&self, target: &Board) -> Transition {
+        let Some(planned) = &self.planned else {
+            return Transition::Full;
+        };
+        let ratio = frame_diff(planned, target).change_ratio();
+        if ratio < self.config.skip_below {
+            Transition::Skip
+        } else if ratio < self.config.partial_below {
+            Transition::Partial
+        } else {
+            Transition::Full
+        }
+    }
+
+    /// Paints `frame`, which with a viewport set is the size of the
+    /// viewport rather than the playfield.
+    pub fn next_frame(&mut self, frame: &Board) -> &FrameReport {
+        let first_step = self.steps.len();
+        let max_pieces = self.config.max_pieces;
+        let budget = |steps: &Vec<Step>| steps.len() - first_step < max_pieces;
+        let transition = self.transition(frame);
+        let viewport = self.config.viewport;
+        let target = &match viewport {
+            Some(viewport) => viewport.place(frame, &self.bot.game.board),
+            None => frame.clone(),
+        };
+
+        if transition == Transition::Full {
+            while budget(&self.steps) {
+                let board = &self.bot.game.board;
+                let (Some(bottom), Some(top)) =
+                    (lowest_wrong_row(board, target), highest_filled_row(board))
+                else {
+                    break;
+                };
+                // clearing takes whole rows, so wipes are scored on all of them
+                if self
+                    .place(&wipe_target(target, bottom, top), target, None)
+                    .is_none()
+                {
+                    break;
+                }
+            }
+        }
+        if transition != Transition::Skip {
+            while budget(&self.steps) && self.place(target, target, viewport).is_some() {}
+            self.planned = Some(frame.clone());
+        }
+
+        let steps = &self.steps[first_step..];
+        self.reports.push(FrameReport {
+            frame: self.reports.len(),
+            transition,
+            first_step,
+            pieces: steps.len(),
+            lines_cleared: steps.iter().map(|step| step.lines_cleared).sum(),
+            comparison: match viewport {
+                Some(viewport) => compare(frame, &viewport.crop(&self.bot.game.board)),
+                None => compare(frame, &self.bot.game.board),
+            },
+        });
+        self.reports.last().unwrap()
+    }
+
+    pub fn run<'a>(&mut self, targets: impl IntoIterator<Item = &'a Board>) {
+        for target in targets {
+            self.next_frame(target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// The bottom row from `from` up to `to`, exclusive.
+    fn bar(from: usize, to: usize) -> Board {
+        let mut board = Board::new(10, 20);
+        board.bulk_add((from..to).map(|col| (0, col)).collect());
+        board
+    }
+
+    #[test]
+    fn wipes_take_every_row_from_the_wrong_cell_up() {
+        let mut target = bar(0, 4);
+        target.add(3, 0);
+        let wipe = wipe_target(&target, 1, 2);
+        assert!((1..=2).all(|row| (0..10).all(|col| wipe.get(row, col))));
+        assert!(wipe.get(0, 3) && !wipe.get(0, 4));
+        assert!(!wipe.get(3, 0));
+    }
+
+    #[test]
+    fn frames_are_skipped_patched_or_redone() {
+        let mut sequencer = Sequencer::new(Bot::new(20, 10, 1), SequenceConfig::default());
+        let placed = Rc::new(Cell::new(0));
+        let seen = placed.clone();
+        sequencer.observe(move |_, _| seen.set(seen.get() + 1));
+
+        let first = sequencer.next_frame(&bar(0, 4)).clone();
+        assert_eq!(first.transition, Transition::Full);
+        assert!(first.pieces > 0);
+        assert!(first.comparison.correct > 0);
+        assert_eq!(placed.get(), first.pieces);
+
+        // the same frame again needs nothing
+        let again = sequencer.next_frame(&bar(0, 4)).clone();
+        assert_eq!((again.transition, again.pieces), (Transition::Skip, 0));
+        assert_eq!(again.first_step, sequencer.steps.len());
+
+        // two cells in two hundred is small enough to patch
+        assert_eq!(
+            sequencer.next_frame(&bar(0, 6)).transition,
+            Transition::Partial
+        );
+        // a whole new picture is not
+        assert_eq!(
+            sequencer.next_frame(&bar(4, 10)).transition,
+            Transition::Full
+        );
+        assert_eq!(placed.get(), sequencer.steps.len());
+        assert_eq!(sequencer.reports.len(), 4);
+    }
+
+    #[test]
+    fn viewport_frames_are_compared_inside_it() {
+        let viewport = Viewport::new(0, 0, 2, 4);
+        let (height, width) = viewport.field(crate::viewport::BUFFER_ROWS);
+        let config = SequenceConfig {
+            viewport: Some(viewport),
+            ..SequenceConfig::default()
+        };
+        let mut sequencer = Sequencer::new(Bot::new(height, width, 1), config);
+        let mut frame = Board::new(4, 2);
+        frame.bulk_add((0..4).map(|col| (0, col)).collect());
+
+        let report = sequencer.next_frame(&frame).clone();
+        let painted = viewport.crop(&sequencer.bot.game.board);
+        assert_eq!(report.comparison, compare(&frame, &painted));
+        assert_eq!(report.comparison.correct + report.comparison.missing, 4);
+    }
+}
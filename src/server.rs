@@ -0,0 +1,252 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::game::Game;
+use crate::piece::{piece_name, Placement};
+use serde_json::{json, Value};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{accept, Message};
+
+const PAGE: &str = include_str!("../web/index.html");
+/// How long a page may take to send its request or read a snapshot before
+/// it is dropped.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Rows bottom first, `.` for empty cells, `#` for filled ones and a piece
+/// letter where the piece is known.
+fn rows(board: &Board) -> Vec<String> {
+    (0..board.height)
+        .map(|row| {
+            (0..board.width)
+                .map(|col| match (board.get(row, col), board.kind(row, col)) {
+                    (true, Some(kind)) => piece_name(kind),
+                    (true, None) => '#',
+                    (false, _) => '.',
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// What the page draws: the board, the active piece and its ghost as cell
+/// lists, the next pieces, hold and the frame being painted.
+pub fn snapshot(game: &Game, target: Option<&Board>) -> Value {
+    let board = &game.board;
+    let cells = |piece: &Placement| Vec::from(piece.abs_locations());
+    let active = board.piece_valid_location(&game.active).then(|| {
+        json!({
+            "type": piece_name(game.active.piece_type).to_string(),
+            "cells": cells(&game.active),
+            "ghost": cells(&board.ghost(&game.active)),
+        })
+    });
+    let queue: String = game
+        .queue
+        .pieces
+        .iter()
+        .take(5)
+        .map(|&piece| piece_name(piece))
+        .collect();
+    json!({
+        "width": board.width,
+        "height": board.height,
+        "board": rows(board),
+        "active": active,
+        "queue": queue,
+        "hold": game.hold.map(|piece| piece_name(piece).to_string()),
+        "target": target.map(rows),
+    })
+}
+
+/// The connected pages, one channel to each page's thread, and the last
+/// snapshot for pages that connect later.
+#[derive(Default)]
+struct Shared {
+    clients: Vec<Sender<String>>,
+    latest: Option<String>,
+}
+
+/// Serves the viewer page over HTTP and pushes snapshots to every page
+/// connected to `/ws`, both on the same port. Pages that connect late get
+/// the latest snapshot straight away. Every connection gets its own thread,
+/// so a slow page only ever holds up itself.
+pub struct Server {
+    shared: Arc<Mutex<Shared>>,
+    pub port: u16,
+}
+
+fn is_upgrade(stream: &TcpStream) -> io::Result<bool> {
+    let mut buf = [0; 2048];
+    let read = stream.peek(&mut buf)?;
+    let request = String::from_utf8_lossy(&buf[..read]).to_ascii_lowercase();
+    Ok(request.contains("upgrade: websocket"))
+}
+
+fn serve_page(mut stream: TcpStream) -> io::Result<()> {
+    // the request itself doesn't matter, every path gets the page
+    let mut buf = [0; 2048];
+    let _ = stream.read(&mut buf)?;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        PAGE.len(),
+        PAGE
+    )
+}
+
+/// Answers one connection: the page, or a socket that gets every snapshot
+/// until it stops taking them.
+fn handle(stream: TcpStream, shared: &Mutex<Shared>) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    if !is_upgrade(&stream)? {
+        return serve_page(stream);
+    }
+    let mut socket = accept(stream).map_err(|error| io::Error::other(error.to_string()))?;
+
+    let (send, snapshots) = channel();
+    {
+        let mut shared = shared.lock().unwrap();
+        if let Some(text) = shared.latest.clone() {
+            let _ = send.send(text);
+        }
+        shared.clients.push(send);
+    }
+    while let Ok(text) = snapshots.recv() {
+        // a page that fell behind skips straight to the newest snapshot
+        let text = snapshots.try_iter().last().unwrap_or(text);
+        if socket.send(Message::text(text)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+impl Server {
+    /// Starts listening on `addr`, e.g. `"127.0.0.1:8080"`; port 0 picks a free one.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let port = listener.local_addr()?.port();
+        let shared: Arc<Mutex<Shared>> = Arc::default();
+
+        let accepted = shared.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                let shared = accepted.clone();
+                thread::spawn(move || handle(stream, &shared));
+            }
+        });
+
+        Ok(Self { shared, port })
+    }
+
+    /// Hands `snapshot` to every connected page, forgetting the ones that
+    /// left. The pages' own threads do the writing, so this never waits on
+    /// the network.
+    pub fn publish(&self, snapshot: &Value) {
+        let text = snapshot.to_string();
+        let mut shared = self.shared.lock().unwrap();
+        shared.latest = Some(text.clone());
+        shared
+            .clients
+            .retain(|send| send.send(text.clone()).is_ok());
+    }
+
+    pub fn publish_game(&self, game: &Game, target: Option<&Board>) {
+        self.publish(&snapshot(game, target));
+    }
+
+    pub fn clients(&self) -> usize {
+        self.shared.lock().unwrap().clients.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use tungstenite::{client, WebSocket};
+
+    fn connect(server: &Server) -> WebSocket<TcpStream> {
+        let stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+        let url = format!("ws://127.0.0.1:{}/ws", server.port);
+        client(url, stream).unwrap().0
+    }
+
+    fn receive(socket: &mut WebSocket<TcpStream>) -> Value {
+        match socket.read().unwrap() {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            message => panic!("unexpected {:?}", message),
+        }
+    }
+
+    fn wait_for_clients(server: &Server, clients: usize) {
+        let start = Instant::now();
+        while server.clients() < clients {
+            assert!(
+                start.elapsed() < TIMEOUT,
+                "only {} clients",
+                server.clients()
+            );
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn serves_the_page() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with(PAGE));
+    }
+
+    #[test]
+    fn broadcasts_past_a_stalled_connection() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        // connects and never finishes its request
+        let _stalled = TcpStream::connect(("127.0.0.1", server.port)).unwrap();
+        let mut first = connect(&server);
+        let mut second = connect(&server);
+        wait_for_clients(&server, 2);
+
+        let game = Game::new(20, 10, 1);
+        server.publish_game(&game, None);
+        for socket in [&mut first, &mut second] {
+            assert_eq!(receive(socket), snapshot(&game, None));
+        }
+    }
+
+    #[test]
+    fn late_pages_get_the_latest_snapshot() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        server.publish(&json!({ "frame": 1 }));
+        server.publish(&json!({ "frame": 2 }));
+        let mut late = connect(&server);
+        assert_eq!(receive(&mut late), json!({ "frame": 2 }));
+    }
+
+    #[test]
+    fn closed_pages_are_forgotten() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let mut page = connect(&server);
+        wait_for_clients(&server, 1);
+        page.close(None).unwrap();
+        drop(page);
+
+        let start = Instant::now();
+        while server.clients() > 0 {
+            assert!(start.elapsed() < TIMEOUT);
+            server.publish(&json!({}));
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
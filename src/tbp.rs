@@ -0,0 +1,363 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::bot::{score, Bot};
+use crate::piece::{piece_from_name, piece_name, Placement};
+use crate::plan::Step;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::io::{stdin, stdout, BufRead, Write};
+
+// Tetris Bot Protocol, one JSON message per line on stdin and stdout. Our
+// placements already use TBP's coordinates: `col` and `row` are its `x` and
+// `y`, and rotation states 0 to 3 are north, east, south and west.
+
+const ORIENTATIONS: [&str; 4] = ["north", "east", "south", "west"];
+const DEPTH: usize = 2;
+const CANDIDATES: usize = 10;
+
+fn piece(value: &Value) -> Option<usize> {
+    let name = value.as_str()?;
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => piece_from_name(c),
+        _ => None,
+    }
+}
+
+fn location(placement: &Placement) -> Value {
+    json!({
+        "type": piece_name(placement.piece_type).to_string(),
+        "orientation": ORIENTATIONS[placement.rotation_state],
+        "x": placement.col,
+        "y": placement.row,
+    })
+}
+
+fn coordinate(value: &Value) -> Option<i8> {
+    value.as_i64()?.try_into().ok()
+}
+
+fn parse_location(value: &Value) -> Option<Placement> {
+    let orientation = value["orientation"].as_str()?;
+    Some(Placement::new(
+        piece(&value["type"])?,
+        ORIENTATIONS.iter().position(|&o| o == orientation)?,
+        coordinate(&value["y"])?,
+        coordinate(&value["x"])?,
+    ))
+}
+
+/// Rows bottom first, each cell `null` or a piece letter (`G` for garbage).
+/// Every row must be as wide as the first.
+fn parse_board(value: &Value) -> Option<Board> {
+    let rows = value.as_array()?;
+    let width = rows.first()?.as_array()?.len();
+    let mut board = Board::new(width, rows.len());
+    for (row, cells) in rows.iter().enumerate() {
+        let cells = cells.as_array()?;
+        if cells.len() != width {
+            return None;
+        }
+        for (col, cell) in cells.iter().enumerate() {
+            match (cell.is_null(), piece(cell)) {
+                (true, _) => {}
+                (false, Some(kind)) => board.paint(row, col, kind),
+                (false, None) => board.add(row, col),
+            }
+        }
+    }
+    Some(board)
+}
+
+/// Rows `0..rows` filled: keeps the stack low and clears lines, which is
+/// the best a painter can do with nothing to paint.
+pub fn default_target(width: usize, height: usize, rows: usize) -> Board {
+    let mut board = Board::new(width, height);
+    for row in 0..rows.min(height) {
+        for col in 0..width {
+            board.add(row, col);
+        }
+    }
+    board
+}
+
+/// Why a `play` message could not be followed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PlayError {
+    /// no game is running, or the queue does not know the piece yet
+    NotStarted,
+    BadLocation,
+    /// neither the current piece nor the one hold would bring
+    WrongPiece,
+    /// out of bounds, overlapping the stack or floating
+    InvalidPlacement,
+}
+
+/// The bot side of TBP. The frontend owns the game, so the board, hold and
+/// known queue are mirrored here and copied into the bot before each search;
+/// pieces the search looks at past the known queue are guesses.
+pub struct TbpBot {
+    target: Option<Board>,
+    bot: Option<Bot>,
+    hold: Option<usize>,
+    /// the current piece first, then the previews
+    queue: VecDeque<usize>,
+}
+
+impl TbpBot {
+    /// Paints `target`, or `default_target` with four rows if there is none.
+    pub fn new(target: Option<Board>) -> Self {
+        Self {
+            target,
+            bot: None,
+            hold: None,
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn info() -> Value {
+        json!({
+            "type": "info",
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "author": env!("CARGO_PKG_NAME"),
+            "features": [],
+        })
+    }
+
+    fn start(&mut self, message: &Value) -> Option<()> {
+        let board = parse_board(&message["board"])?;
+        self.hold = piece(&message["hold"]);
+        self.queue = message["queue"]
+            .as_array()?
+            .iter()
+            .map(piece)
+            .collect::<Option<_>>()?;
+
+        let mut bot = Bot::new(board.height, board.width, 1);
+        bot.game.board = board;
+        self.bot = Some(bot);
+        Some(())
+    }
+
+    /// Copies the mirrored state into the bot's game.
+    fn sync(&mut self) -> Option<&mut Bot> {
+        let bot = self.bot.as_mut()?;
+        let (&current, rest) = (self.queue.front()?, self.queue.range(1..));
+        bot.game.active = bot.game.new_piece(current);
+        bot.game.hold = self.hold;
+        bot.game.queue.pieces = rest.copied().collect();
+        Some(bot)
+    }
+
+    fn suggest(&mut self) -> Value {
+        let target = self.target.clone();
+        // with nothing held and no previews, hold would swap in a piece the
+        // frontend has not dealt yet
+        let guessing = self.hold.is_none() && self.queue.len() < 2;
+        let Some(bot) = self.sync() else {
+            return json!({ "type": "suggestion", "moves": [] });
+        };
+        let board = &bot.game.board;
+        let target = target.unwrap_or_else(|| default_target(board.width, board.height, 4));
+        let eval = |step: &Step| match step.hold {
+            true if guessing => i32::MAX / 4,
+            _ => score(&vec![step.placement], &target) as i32,
+        };
+
+        let moves: Vec<Value> = bot
+            .best_plan_by(DEPTH, CANDIDATES, &eval)
+            .and_then(|plan| plan.first().map(|step| step.placement))
+            .map(|placement| {
+                let placement = bot.game.board.ghost(&placement);
+                // spins are not detected, TBP frontends only use this for scoring
+                json!({ "location": location(&placement), "spin": "none" })
+            })
+            .into_iter()
+            .collect();
+        json!({ "type": "suggestion", "moves": moves })
+    }
+
+    /// Follows the frontend's move. Nothing changes unless it is one the
+    /// mirrored game allows.
+    fn play(&mut self, message: &Value) -> Result<(), PlayError> {
+        let placement =
+            parse_location(&message["move"]["location"]).ok_or(PlayError::BadLocation)?;
+        let bot = self.bot.as_mut().ok_or(PlayError::NotStarted)?;
+        let current = *self.queue.front().ok_or(PlayError::NotStarted)?;
+
+        // pieces taken off the queue, and the hold afterwards
+        let (used, hold) = match self.hold {
+            _ if placement.piece_type == current => (1, self.hold),
+            Some(held) if held == placement.piece_type => (1, Some(current)),
+            Some(_) => return Err(PlayError::WrongPiece),
+            None => match self.queue.get(1) {
+                Some(&next) if next == placement.piece_type => (2, Some(current)),
+                Some(_) => return Err(PlayError::WrongPiece),
+                None => return Err(PlayError::NotStarted),
+            },
+        };
+        if !bot.game.board.piece_valid_placement(&placement) {
+            return Err(PlayError::InvalidPlacement);
+        }
+
+        self.queue.drain(..used);
+        self.hold = hold;
+        for [row, col] in placement.abs_locations() {
+            bot.game
+                .board
+                .paint(row as usize, col as usize, placement.piece_type);
+        }
+        for row in (0..bot.game.board.height).rev() {
+            bot.game.board.line_clear(row);
+        }
+        Ok(())
+    }
+
+    /// Handles one message, returning the reply if it needs one.
+    pub fn handle(&mut self, message: &Value) -> Option<Value> {
+        match message["type"].as_str()? {
+            "rules" => Some(json!({ "type": "ready" })),
+            "start" => {
+                if self.start(message).is_none() {
+                    eprintln!("tbp: bad start message");
+                }
+                None
+            }
+            "suggest" => Some(self.suggest()),
+            "play" => {
+                // TBP has no reply to `play`, so a move that cannot be
+                // followed is only logged
+                if let Err(error) = self.play(message) {
+                    eprintln!("tbp: ignored play message ({:?})", error);
+                }
+                None
+            }
+            "new_piece" => {
+                if let Some(piece) = piece(&message["piece"]) {
+                    self.queue.push_back(piece);
+                }
+                None
+            }
+            "stop" => {
+                self.bot = None;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Talks TBP over stdin and stdout until `quit` or the end of input.
+    pub fn run(&mut self) {
+        let mut out = stdout().lock();
+        let mut send = |message: Value| {
+            let _ = writeln!(out, "{}", message);
+            let _ = out.flush();
+        };
+        send(Self::info());
+
+        for line in stdin().lock().lines().map_while(Result::ok) {
+            let message: Value = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(error) => {
+                    eprintln!("tbp: {}", error);
+                    continue;
+                }
+            };
+            if message["type"] == "quit" {
+                break;
+            }
+            if let Some(reply) = self.handle(&message) {
+                send(reply);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started(queue: &str, hold: Value) -> TbpBot {
+        let mut bot = TbpBot::new(None);
+        let queue: Vec<String> = queue.chars().map(String::from).collect();
+        let board = vec![vec![Value::Null; 10]; 40];
+        let start = json!({ "type": "start", "board": board, "hold": hold, "queue": queue });
+        assert_eq!(bot.handle(&start), None);
+        bot
+    }
+
+    fn play(piece: &str, orientation: &str, x: i64, y: i64) -> Value {
+        let location = json!({ "type": piece, "orientation": orientation, "x": x, "y": y });
+        json!({ "type": "play", "move": { "location": location } })
+    }
+
+    #[test]
+    fn plays_its_own_suggestion() {
+        let mut bot = started("TIOSZ", Value::Null);
+        let suggestion = bot.handle(&json!({ "type": "suggest" })).unwrap();
+        let location = &suggestion["moves"][0]["location"];
+        let reply = bot.handle(&json!({ "type": "play", "move": { "location": location } }));
+        assert_eq!(reply, None);
+        // the suggestion may have held the T for the I
+        let used = 5 - bot.queue.len();
+        assert_eq!(bot.hold.is_some(), used == 2);
+        let board = &bot.bot.as_ref().unwrap().game.board;
+        let placement = parse_location(location).unwrap();
+        for [row, col] in placement.abs_locations() {
+            assert!(board.get(row as usize, col as usize));
+        }
+    }
+
+    #[test]
+    fn play_through_hold() {
+        let mut bot = started("TIO", Value::Null);
+        assert_eq!(bot.handle(&play("I", "north", 4, 0)), None);
+        assert_eq!(bot.hold, Some(6));
+        assert_eq!(bot.queue, VecDeque::from([2]));
+    }
+
+    #[test]
+    fn no_hold_without_previews() {
+        // any piece fits the square better than the I, but the frontend has
+        // not said which piece comes next
+        let mut target = Board::new(10, 40);
+        target.bulk_add(vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+        let mut bot = started("I", Value::Null);
+        bot.target = Some(target);
+        let suggestion = bot.handle(&json!({ "type": "suggest" })).unwrap();
+        assert_eq!(suggestion["moves"][0]["location"]["type"], "I");
+
+        bot.handle(&json!({ "type": "new_piece", "piece": "O" }));
+        let suggestion = bot.handle(&json!({ "type": "suggest" })).unwrap();
+        assert_eq!(suggestion["moves"][0]["location"]["type"], "O");
+    }
+
+    #[test]
+    fn bad_moves_are_ignored() {
+        for (piece, x, y, error) in [
+            ("T", 300, 0, PlayError::BadLocation),
+            ("T", 0, 0, PlayError::InvalidPlacement),
+            ("T", 4, 5, PlayError::InvalidPlacement),
+            ("L", 4, 0, PlayError::WrongPiece),
+        ] {
+            let mut bot = started("TIO", json!("S"));
+            let message = play(piece, "north", x, y);
+            assert_eq!(bot.play(&message), Err(error));
+            assert_eq!(bot.handle(&message), None);
+            assert_eq!(bot.hold, Some(3));
+            assert_eq!(bot.queue, VecDeque::from([6, 4, 2]));
+            assert!(bot.bot.is_some());
+        }
+    }
+
+    #[test]
+    fn ragged_boards_are_rejected() {
+        let board = json!([[null, null], [null, null, null]]);
+        assert!(parse_board(&board).is_none());
+        let board = json!([[null, "G"], [null, "T"]]);
+        let board = parse_board(&board).unwrap();
+        assert_eq!((board.get(0, 1), board.kind(1, 1)), (true, Some(6)));
+    }
+}
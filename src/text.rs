@@ -0,0 +1,243 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::game::Game;
+use crate::piece::{piece_from_name, piece_name};
+use std::str::FromStr;
+
+// A board written top row first, `.` for empty cells, `#` for filled ones
+// and a piece letter for cells filled by a known piece, under an optional
+// `key: value` header:
+//
+//     height: 6
+//     hold: T
+//     queue: IOSZ
+//     ....##....
+//     #.########
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    UnknownKey(String),
+    BadValue(String),
+    BadCell(char),
+    RaggedRow(usize),
+    TooManyRows,
+    Empty,
+}
+
+#[derive(Default)]
+struct Header {
+    width: Option<usize>,
+    height: Option<usize>,
+    active: Option<usize>,
+    hold: Option<usize>,
+    queue: Vec<usize>,
+}
+
+fn piece(value: &str) -> Result<usize, ParseError> {
+    let mut chars = value.chars();
+    match (chars.next().and_then(piece_from_name), chars.next()) {
+        (Some(piece), None) => Ok(piece),
+        _ => Err(ParseError::BadValue(value.to_string())),
+    }
+}
+
+fn number(value: &str) -> Result<usize, ParseError> {
+    value.parse().map_err(|_| ParseError::BadValue(value.to_string()))
+}
+
+fn parse(text: &str) -> Result<(Header, Board), ParseError> {
+    let mut header = Header::default();
+    let mut rows: Vec<Vec<Option<Option<usize>>>> = Vec::new();
+
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                "width" => header.width = Some(number(value)?),
+                "height" => header.height = Some(number(value)?),
+                "active" => header.active = Some(piece(value)?),
+                "hold" => header.hold = Some(piece(value)?),
+                "queue" => {
+                    header.queue = value
+                        .chars()
+                        .map(|c| piece_from_name(c).ok_or(ParseError::BadValue(value.to_string())))
+                        .collect::<Result<_, _>>()?
+                }
+                key => return Err(ParseError::UnknownKey(key.to_string())),
+            }
+            continue;
+        }
+
+        let row = line
+            .chars()
+            .map(|c| match c {
+                '.' => Ok(None),
+                '#' => Ok(Some(None)),
+                c => piece_from_name(c).map(|kind| Some(Some(kind))).ok_or(ParseError::BadCell(c)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.push(row);
+    }
+
+    let width = match (header.width, rows.first()) {
+        (Some(width), _) => width,
+        (None, Some(row)) => row.len(),
+        (None, None) => return Err(ParseError::Empty),
+    };
+    let height = header.height.unwrap_or(rows.len());
+    if rows.len() > height {
+        return Err(ParseError::TooManyRows);
+    }
+
+    let mut board = Board::new(width, height);
+    for (row, cells) in rows.iter().rev().enumerate() {
+        if cells.len() != width {
+            return Err(ParseError::RaggedRow(rows.len() - row));
+        }
+        for (col, &cell) in cells.iter().enumerate() {
+            match cell {
+                Some(Some(kind)) => board.paint(row, col, kind),
+                Some(None) => board.add(row, col),
+                None => {}
+            }
+        }
+    }
+    Ok((header, board))
+}
+
+impl FromStr for Board {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse(text).map(|(_, board)| board)
+    }
+}
+
+/// Boards serialize as their text form, which is far smaller than the raw
+/// cell and kind grids and still readable in a JSON file.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Board {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&board_to_text(self))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Board {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse()
+            .map_err(|error| serde::de::Error::custom(format!("bad board: {:?}", error)))
+    }
+}
+
+/// Builds a game from a text board. Without an `active` entry the first
+/// piece of `queue` becomes active; pieces past the queue come from `seed`.
+pub fn parse_game(text: &str, seed: usize) -> Result<Game, ParseError> {
+    let (header, board) = parse(text)?;
+    let mut game = Game::new(board.height, board.width, seed);
+    game.board = board;
+    game.hold = header.hold;
+
+    let mut queue = header.queue.into_iter();
+    if let Some(active) = header.active.or_else(|| queue.next()) {
+        game.active = game.new_piece(active);
+    }
+    game.queue.pieces = queue.chain(game.queue.pieces.iter().copied()).collect();
+    Ok(game)
+}
+
+pub fn board_to_text(board: &Board) -> String {
+    let mut out = format!("width: {}\nheight: {}\n", board.width, board.height);
+    let top = (0..board.height)
+        .rev()
+        .find(|&row| (0..board.width).any(|col| board.get(row, col)));
+    for row in (0..top.map_or(0, |top| top + 1)).rev() {
+        for col in 0..board.width {
+            out.push(match (board.get(row, col), board.kind(row, col)) {
+                (true, Some(kind)) => piece_name(kind),
+                (true, None) => '#',
+                (false, _) => '.',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn game_to_text(game: &Game) -> String {
+    let mut out = format!("active: {}\n", piece_name(game.active.piece_type));
+    if let Some(hold) = game.hold {
+        out.push_str(&format!("hold: {}\n", piece_name(hold)));
+    }
+    let queue: String = game.queue.pieces.iter().map(|&piece| piece_name(piece)).collect();
+    out.push_str(&format!("queue: {}\n", queue));
+    out + &board_to_text(&game.board)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn board_round_trip() {
+        let mut board = Board::new(10, 8);
+        board.bulk_add(vec![(0, 0), (0, 1), (0, 2), (1, 9)]);
+        for (row, col, kind) in [(0, 4, 0), (0, 5, 4), (1, 4, 6), (3, 0, 2)] {
+            board.paint(row, col, kind);
+        }
+        let text = board_to_text(&board);
+        assert_eq!(text.parse::<Board>(), Ok(board));
+        assert_eq!(
+            text,
+            "width: 10\nheight: 8\nO.........\n..........\n....T....#\n###.ZI....\n"
+        );
+    }
+
+    #[test]
+    fn empty_board_keeps_its_size() {
+        let board = Board::new(4, 5);
+        assert_eq!(board_to_text(&board).parse::<Board>(), Ok(board));
+    }
+
+    #[test]
+    fn game_round_trip() {
+        let mut game = parse_game("hold: T\nqueue: IOSZL\n....\n#.##\n", 1).unwrap();
+        assert_eq!(game.active.piece_type, piece_from_name('I').unwrap());
+        assert_eq!(game.hold, piece_from_name('T'));
+        let again = parse_game(&game_to_text(&game), 7).unwrap();
+
+        assert_eq!(again.board, game.board);
+        assert_eq!(again.active, game.active);
+        assert_eq!(again.hold, game.hold);
+        let mut queue = again.queue.clone();
+        for _ in 0..game.queue.pieces.len() {
+            assert_eq!(queue.next(), game.queue.next());
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("..\n...".parse::<Board>(), Err(ParseError::RaggedRow(2)));
+        assert_eq!("..\n.x".parse::<Board>(), Err(ParseError::BadCell('x')));
+        assert_eq!("height: 1\n..\n..".parse::<Board>(), Err(ParseError::TooManyRows));
+        assert_eq!(
+            "depth: 3\n..".parse::<Board>(),
+            Err(ParseError::UnknownKey("depth".to_string()))
+        );
+        assert_eq!("".parse::<Board>(), Err(ParseError::Empty));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn boards_serialize_as_text() {
+        let mut board = Board::new(4, 6);
+        board.bulk_add(vec![(0, 0), (0, 1)]);
+        board.paint(0, 3, 5);
+        let json = serde_json::to_string(&board).unwrap();
+        assert_eq!(json, r#""width: 4\nheight: 6\n##.J\n""#);
+        assert_eq!(serde_json::from_str::<Board>(&json).unwrap(), board);
+        assert!(serde_json::from_str::<Board>(r#""..\n...""#).is_err());
+    }
+}
@@ -0,0 +1,405 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::bot::score;
+use crate::game::Game;
+use crate::piece::{Placement, Point};
+use crate::plan::Plan;
+use crate::schedule::{schedule, Blocker, ScheduleConfig, ScheduleError};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Copy, Clone, Debug)]
+pub struct TilingConfig {
+    /// exact covers to try ordering before giving up
+    pub max_tilings: usize,
+    pub schedule: ScheduleConfig,
+}
+
+impl Default for TilingConfig {
+    fn default() -> Self {
+        Self {
+            max_tilings: 1000,
+            schedule: ScheduleConfig::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TilingError {
+    /// the cells left to fill don't come in fours
+    Cells(usize),
+    /// no exact cover uses only pieces the queue can supply
+    NoTiling,
+    /// none of the covers found can be scheduled; `furthest` is where the
+    /// one that got furthest was stuck
+    NoOrder { tilings: usize, furthest: Blocker },
+    /// the covers can't be scheduled whatever the order, say because the
+    /// picture and the board fill a row between them
+    Schedule(ScheduleError),
+}
+
+/// Every placement that lies entirely on target cells the board hasn't
+/// filled yet, one per set of cells.
+pub fn tiles(board: &Board, target: &Board) -> Vec<Placement> {
+    let open = |[row, col]: Point| {
+        row >= 0
+            && col >= 0
+            && (row as usize) < target.height
+            && (col as usize) < target.width
+            && target.get(row as usize, col as usize)
+            && !board.get(row as usize, col as usize)
+    };
+
+    let mut seen = HashSet::new();
+    let mut out = Vec::new();
+    for piece_type in 0..7 {
+        for rotation in 0..4 {
+            for row in -2..target.height as i8 + 2 {
+                for col in -2..target.width as i8 + 2 {
+                    let placement = Placement::new(piece_type, rotation, row, col);
+                    let cells = placement.abs_locations();
+                    if cells.iter().all(|&cell| open(cell)) && seen.insert(cells) {
+                        out.push(placement);
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Dancing links over the cells to fill. Node 0 is the root, nodes
+/// `1..=columns` head the columns and every tile adds one node per cell.
+struct Links {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    size: Vec<usize>,
+    tile: Vec<usize>,
+}
+
+impl Links {
+    fn new(columns: usize, rows: &[[usize; 4]]) -> Self {
+        let headers = columns + 1;
+        let mut links = Self {
+            left: (0..headers).map(|i| (i + headers - 1) % headers).collect(),
+            right: (0..headers).map(|i| (i + 1) % headers).collect(),
+            up: (0..headers).collect(),
+            down: (0..headers).collect(),
+            column: (0..headers).collect(),
+            size: vec![0; headers],
+            tile: vec![usize::MAX; headers],
+        };
+        for (tile, cells) in rows.iter().enumerate() {
+            let first = links.column.len();
+            for (i, &cell) in cells.iter().enumerate() {
+                let node = first + i;
+                let column = cell + 1;
+                links.left.push(first + (i + 3) % 4);
+                links.right.push(first + (i + 1) % 4);
+                links.up.push(links.up[column]);
+                links.down.push(column);
+                let above = links.up[column];
+                links.down[above] = node;
+                links.up[column] = node;
+                links.column.push(column);
+                links.tile.push(tile);
+                links.size[column] += 1;
+            }
+        }
+        links
+    }
+
+    fn cover(&mut self, column: usize) {
+        let (l, r) = (self.left[column], self.right[column]);
+        self.right[l] = r;
+        self.left[r] = l;
+        let mut row = self.down[column];
+        while row != column {
+            let mut node = self.right[row];
+            while node != row {
+                let (u, d) = (self.up[node], self.down[node]);
+                self.down[u] = d;
+                self.up[d] = u;
+                self.size[self.column[node]] -= 1;
+                node = self.right[node];
+            }
+            row = self.down[row];
+        }
+    }
+
+    fn uncover(&mut self, column: usize) {
+        let mut row = self.up[column];
+        while row != column {
+            let mut node = self.left[row];
+            while node != row {
+                let (u, d) = (self.up[node], self.down[node]);
+                self.down[u] = node;
+                self.up[d] = node;
+                self.size[self.column[node]] += 1;
+                node = self.left[node];
+            }
+            row = self.up[row];
+        }
+        let (l, r) = (self.left[column], self.right[column]);
+        self.right[l] = column;
+        self.left[r] = column;
+    }
+
+    /// Algorithm X, always branching on the column with the fewest tiles
+    /// left. `kinds` gives each tile's piece type and no more than `caps`
+    /// of a type are used. Stops once `visit` returns true.
+    fn search(
+        &mut self,
+        kinds: &[usize],
+        caps: &[usize; 7],
+        counts: &mut [usize; 7],
+        chosen: &mut Vec<usize>,
+        visit: &mut dyn FnMut(&[usize]) -> bool,
+    ) -> bool {
+        if self.right[0] == 0 {
+            return visit(chosen);
+        }
+        let mut column = self.right[0];
+        let mut best = column;
+        while column != 0 {
+            if self.size[column] < self.size[best] {
+                best = column;
+            }
+            column = self.right[column];
+        }
+        if self.size[best] == 0 {
+            return false;
+        }
+
+        self.cover(best);
+        let mut row = self.down[best];
+        let mut stop = false;
+        while row != best && !stop {
+            let kind = kinds[self.tile[row]];
+            if counts[kind] < caps[kind] {
+                counts[kind] += 1;
+                chosen.push(self.tile[row]);
+                let mut node = self.right[row];
+                while node != row {
+                    self.cover(self.column[node]);
+                    node = self.right[node];
+                }
+
+                stop = self.search(kinds, caps, counts, chosen, visit);
+
+                let mut node = self.left[row];
+                while node != row {
+                    self.uncover(self.column[node]);
+                    node = self.left[node];
+                }
+                chosen.pop();
+                counts[kind] -= 1;
+            }
+            row = self.down[row];
+        }
+        self.uncover(best);
+        stop
+    }
+}
+
+/// Calls `visit` with each exact cover of the target cells `board` hasn't
+/// filled, using at most `caps[t]` pieces of type `t`, until it returns true.
+pub fn exact_covers(
+    board: &Board,
+    target: &Board,
+    caps: &[usize; 7],
+    visit: &mut dyn FnMut(&[Placement]) -> bool,
+) {
+    let tiles = tiles(board, target);
+    let mut columns = HashMap::new();
+    for row in 0..target.height {
+        for col in 0..target.width {
+            if target.get(row, col) && !board.get(row, col) {
+                let next = columns.len();
+                columns.insert([row as i8, col as i8], next);
+            }
+        }
+    }
+    let rows: Vec<[usize; 4]> = tiles
+        .iter()
+        .map(|tile| tile.abs_locations().map(|cell| columns[&cell]))
+        .collect();
+    let kinds: Vec<usize> = tiles.iter().map(|tile| tile.piece_type).collect();
+
+    let mut links = Links::new(columns.len(), &rows);
+    let mut chosen = Vec::new();
+    links.search(&kinds, caps, &mut [0; 7], &mut chosen, &mut |cover| {
+        let cover: Vec<Placement> = cover.iter().map(|&i| tiles[i]).collect();
+        visit(&cover)
+    });
+}
+
+/// Paints `target` from `game` with pieces that fill exactly its cells: the
+/// target's open cells are tiled by exact cover, then the tiles are handed
+/// to `schedule` to find an order the queue (with hold) can supply. Cells
+/// already on the board are kept and can hold pieces up.
+pub fn tile(game: &Game, target: &Board, config: &TilingConfig) -> Result<Plan, TilingError> {
+    let board = &game.board;
+    let open = (0..target.height)
+        .flat_map(|row| (0..target.width).map(move |col| (row, col)))
+        .filter(|&(row, col)| target.get(row, col) && !board.get(row, col))
+        .count();
+    if !open.is_multiple_of(4) {
+        return Err(TilingError::Cells(open));
+    }
+
+    // a cover can only use pieces the tiles and fillers take from the
+    // queue, plus one more as hold lets any of them wait
+    let taken = open / 4 + config.schedule.max_fillers + 1;
+    let mut queue = game.queue.clone();
+    let upcoming =
+        std::iter::once(game.active.piece_type).chain(std::iter::from_fn(|| Some(queue.next())));
+    let mut caps = [0; 7];
+    for piece in game.hold.into_iter().chain(upcoming).take(taken) {
+        caps[piece] += 1;
+    }
+
+    let mut tilings = 0;
+    let mut out = None;
+    let mut furthest: Option<Blocker> = None;
+    let mut error = None;
+    exact_covers(board, target, &caps, &mut |cover| {
+        tilings += 1;
+        match schedule(game, cover, &config.schedule) {
+            Ok(schedule) => out = Some(schedule.plan),
+            Err(ScheduleError::Blocked(blocker)) => {
+                if furthest
+                    .as_ref()
+                    .is_none_or(|best| blocker.placed > best.placed)
+                {
+                    furthest = Some(blocker);
+                }
+            }
+            // the board and every cover fill the same cells, so another
+            // cover would fail the same way
+            Err(other) => error = Some(other),
+        }
+        out.is_some() || error.is_some() || tilings >= config.max_tilings
+    });
+
+    if let Some(error) = error {
+        return Err(TilingError::Schedule(error));
+    }
+    match (out, furthest) {
+        (Some(mut plan), _) => {
+            plan.evaluation = plan
+                .steps
+                .iter()
+                .map(|step| score(&vec![step.placement], target) as i32)
+                .sum();
+            Ok(plan)
+        }
+        (None, Some(furthest)) => Err(TilingError::NoOrder { tilings, furthest }),
+        (None, None) => Err(TilingError::NoTiling),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::piece_from_name;
+
+    /// The bottom-left `rows` by `cols` corner of a 10 by 20 board.
+    fn corner(rows: usize, cols: usize) -> Board {
+        let mut target = Board::new(10, 20);
+        for row in 0..rows {
+            for col in 0..cols {
+                target.add(row, col);
+            }
+        }
+        target
+    }
+
+    /// An empty game with `queue` dealt first.
+    fn dealt(queue: &str) -> Game {
+        let mut game = Game::new(20, 10, 1);
+        let mut pieces = queue.chars().map(|c| piece_from_name(c).unwrap());
+        game.active = game.new_piece(pieces.next().unwrap());
+        game.queue.pieces = pieces.chain(game.queue.pieces.iter().copied()).collect();
+        game
+    }
+
+    fn count(target: &Board, caps: &[usize; 7]) -> usize {
+        let cells: usize = target.arr.iter().flatten().filter(|&&cell| cell).count();
+        let mut covers = 0;
+        exact_covers(&Board::new(10, 20), target, caps, &mut |cover| {
+            assert_eq!(cover.len() * 4, cells);
+            covers += 1;
+            false
+        });
+        covers
+    }
+
+    #[test]
+    fn covers_count_the_tilings() {
+        // tetromino tilings of a 2x4 and a 4x4 rectangle, counted by brute force
+        assert_eq!(count(&corner(2, 4), &[4; 7]), 4);
+        assert_eq!(count(&corner(4, 4), &[4; 7]), 117);
+        assert_eq!(count(&corner(2, 8), &[4; 7]), 25);
+    }
+
+    #[test]
+    fn caps_limit_the_pieces() {
+        let only = |name: char, count: usize| {
+            let mut caps = [0; 7];
+            caps[piece_from_name(name).unwrap()] = count;
+            caps
+        };
+        assert_eq!(count(&corner(2, 4), &only('O', 2)), 1);
+        assert_eq!(count(&corner(2, 4), &only('I', 2)), 1);
+        assert_eq!(count(&corner(2, 4), &only('O', 1)), 0);
+        assert_eq!(count(&corner(2, 4), &only('T', 4)), 0);
+    }
+
+    #[test]
+    fn cells_on_the_board_are_left_out() {
+        let mut board = Board::new(10, 20);
+        board.add(0, 0);
+        board.add(0, 1);
+        board.add(1, 0);
+        board.add(1, 1);
+        let mut covers = Vec::new();
+        exact_covers(&board, &corner(2, 4), &[4; 7], &mut |cover| {
+            covers.push(cover.to_vec());
+            false
+        });
+        assert_eq!(covers, vec![vec![Placement::new(2, 0, 0, 2)]]);
+    }
+
+    #[test]
+    fn tile_paints_the_target() {
+        let target = corner(2, 4);
+        let plan = tile(&dealt("OO"), &target, &TilingConfig::default()).unwrap();
+        assert_eq!(plan.steps.len(), 2);
+        assert_eq!(plan.steps[1].board.arr, target.arr);
+    }
+
+    #[test]
+    fn impossible_targets_are_reported() {
+        let config = TilingConfig::default();
+        let mut three = Board::new(10, 20);
+        three.bulk_add(vec![(0, 0), (0, 1), (0, 2)]);
+        assert_eq!(
+            tile(&dealt("OO"), &three, &config).unwrap_err(),
+            TilingError::Cells(3)
+        );
+        // five O pieces tile the bottom two rows, which would clear
+        assert_eq!(
+            tile(&dealt("OOOOO"), &corner(2, 10), &config).unwrap_err(),
+            TilingError::Schedule(ScheduleError::FullRow(0))
+        );
+        // two T pieces can't make a 2x4 box
+        assert_eq!(
+            tile(&dealt("TTT"), &corner(2, 4), &config).unwrap_err(),
+            TilingError::NoTiling
+        );
+    }
+}
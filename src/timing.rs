@@ -0,0 +1,517 @@
+#![allow(dead_code)]
+
+use crate::control::*;
+use crate::finesse::Key;
+use crate::game::Game;
+use crate::piece::Placement;
+use crate::plan::{Plan, Step};
+use std::collections::VecDeque;
+
+/// Real-time rules, all measured in frames (60 per second).
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimingConfig {
+    /// rows fallen per frame; 20 or more is instant
+    pub gravity: f32,
+    pub lock_delay: u32,
+    /// how many moves or rotations on the ground may restart the lock delay
+    pub move_resets: u32,
+    pub are: u32,
+    pub line_clear_delay: u32,
+    pub das: u32,
+    pub arr: u32,
+    /// soft drop speed as a multiple of gravity
+    pub soft_drop_factor: f32,
+}
+
+impl Default for TimingConfig {
+    fn default() -> Self {
+        Self {
+            gravity: 0.02,
+            lock_delay: 30,
+            move_resets: 15,
+            are: 0,
+            line_clear_delay: 0,
+            das: 10,
+            arr: 2,
+            soft_drop_factor: 20.0,
+        }
+    }
+}
+
+impl TimingConfig {
+    /// Whether soft drop reaches the floor at once, the only way a sonic
+    /// drop can be typed.
+    pub fn instant_soft_drop(&self) -> bool {
+        self.gravity * self.soft_drop_factor >= 20.0
+    }
+
+    /// Frames a key stays held; a soft drop moves the piece down over them,
+    /// and a DAS shift moves one column on the press, waits out DAS, then
+    /// moves a column every ARR frames until it reaches the wall.
+    fn hold_frames(&self, command: &Command) -> u32 {
+        let drop_frames = |rows: i8| {
+            let speed = (self.gravity * self.soft_drop_factor).max(f32::EPSILON);
+            (rows as f32 / speed).ceil() as u32
+        };
+        match command {
+            Command::DasShift(shift) if shift.distance() > 1 => {
+                self.das + self.arr * (shift.distance() as u32 - 2) + 1
+            }
+            Command::SoftDrop(drop) => drop_frames(drop.distance()),
+            Command::PartialSoftDrop(drop) => drop_frames(drop.distance()),
+            Command::SonicDrop(drop) => drop_frames(drop.distance()),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimedInput {
+    pub frame: u32,
+    pub key: Key,
+}
+
+/// A key as it was actually pressed: down on `frame`, up `frames` later.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Press {
+    pub frame: u32,
+    pub key: Key,
+    pub frames: u32,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lock {
+    pub frame: u32,
+    pub placement: Placement,
+    pub lines_cleared: usize,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TimingError {
+    /// piece `step` locked somewhere other than planned, e.g. because
+    /// gravity or lock delay beat the inputs
+    Diverged {
+        step: usize,
+        frame: u32,
+        expected: Placement,
+        actual: Placement,
+    },
+    /// the inputs ran out (or `max_frames` passed) before every step locked
+    Unfinished { locked: usize, expected: usize },
+    /// step `step` has inputs no key produces, such as engine-only commands,
+    /// or a sonic drop while soft drop isn't instant
+    Untypeable { step: usize },
+}
+
+/// Drives a `Game` one frame at a time with gravity, lock delay and entry
+/// delays. Inputs that arrive while the game is busy (during ARE, or while
+/// an earlier key is still held) wait until it can take them.
+pub struct Simulator {
+    pub game: Game,
+    pub config: TimingConfig,
+    pub frame: u32,
+    pub presses: Vec<Press>,
+    pub locks: Vec<Lock>,
+    pending: VecDeque<TimedInput>,
+    fall: f32,
+    /// rows a held soft drop has yet to move the piece
+    soft_drop: i8,
+    /// columns a held DAS shift has yet to move the piece, which way, and
+    /// frames until the next one
+    das_shift: i8,
+    das_dx: i8,
+    das_timer: u32,
+    lock_timer: u32,
+    resets: u32,
+    delay: u32,
+    busy_until: u32,
+}
+
+impl Simulator {
+    pub fn new(game: Game, config: TimingConfig) -> Self {
+        Self {
+            game,
+            config,
+            frame: 0,
+            presses: Vec::new(),
+            locks: Vec::new(),
+            pending: VecDeque::new(),
+            fall: 0.0,
+            soft_drop: 0,
+            das_shift: 0,
+            das_dx: 0,
+            das_timer: 0,
+            lock_timer: 0,
+            resets: 0,
+            delay: 0,
+            busy_until: 0,
+        }
+    }
+
+    pub fn feed(&mut self, inputs: impl IntoIterator<Item = TimedInput>) {
+        self.pending.extend(inputs);
+    }
+
+    pub fn idle(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    pub fn tick(&mut self) {
+        if self.delay > 0 {
+            self.delay -= 1;
+        } else {
+            self.handle_inputs();
+            self.apply_das();
+            self.apply_gravity();
+        }
+        self.frame += 1;
+    }
+
+    pub fn run(&mut self, max_frames: u32) {
+        while !self.idle() && self.frame < max_frames {
+            self.tick();
+        }
+    }
+
+    fn handle_inputs(&mut self) {
+        while self.frame >= self.busy_until {
+            match self.pending.front() {
+                Some(input) if input.frame <= self.frame => {}
+                _ => return,
+            }
+            let key = self.pending.pop_front().unwrap().key;
+            let mut command = key.command();
+
+            if key == Key::HardDrop {
+                self.lock();
+                self.presses.push(Press {
+                    frame: self.frame,
+                    key,
+                    frames: 1,
+                });
+                self.busy_until = self.frame + 1;
+                return;
+            }
+
+            let grounded = self.game.board.piece_grounded(&self.game.active);
+            let moved = command.execute(&mut self.game);
+            if let Command::SoftDrop(_) | Command::PartialSoftDrop(_) | Command::SonicDrop(_) =
+                command
+            {
+                // the piece comes down over the frames the key is held, not at once
+                command.undo(&mut self.game);
+                self.soft_drop = match &command {
+                    Command::SoftDrop(drop) => drop.distance(),
+                    Command::PartialSoftDrop(drop) => drop.distance(),
+                    Command::SonicDrop(drop) => drop.distance(),
+                    _ => 0,
+                };
+            }
+            let moved = match &mut command {
+                Command::DasShift(shift) => {
+                    // only the first column comes on the press
+                    shift.undo(&mut self.game);
+                    self.das_shift = (shift.distance() - 1).max(0);
+                    self.das_dx = shift.dx();
+                    self.das_timer = self.config.das;
+                    shift.distance() > 0 && PieceMove::new(0, shift.dx()).execute(&mut self.game)
+                }
+                _ => moved,
+            };
+            if key == Key::Hold {
+                self.reset_piece();
+            } else if moved && grounded {
+                self.reset_lock();
+            }
+
+            // one key down per frame, however short the press
+            let frames = self.config.hold_frames(&command).max(1);
+            self.presses.push(Press {
+                frame: self.frame,
+                key,
+                frames,
+            });
+            self.busy_until = self.frame + frames;
+        }
+    }
+
+    fn reset_lock(&mut self) {
+        if self.resets < self.config.move_resets {
+            self.resets += 1;
+            self.lock_timer = 0;
+        }
+    }
+
+    fn apply_das(&mut self) {
+        while self.das_shift > 0 && self.das_timer == 0 {
+            self.das_shift -= 1;
+            self.das_timer = self.config.arr;
+            let grounded = self.game.board.piece_grounded(&self.game.active);
+            if PieceMove::new(0, self.das_dx).execute(&mut self.game) && grounded {
+                self.reset_lock();
+            }
+        }
+        if self.das_shift > 0 {
+            self.das_timer -= 1;
+        }
+    }
+
+    fn apply_gravity(&mut self) {
+        self.fall += if self.soft_drop > 0 {
+            self.config.gravity * self.config.soft_drop_factor
+        } else {
+            self.config.gravity
+        };
+        while self.fall >= 1.0 {
+            self.fall -= 1.0;
+            if !PieceMove::new(-1, 0).execute(&mut self.game) {
+                self.fall = 0.0;
+                self.soft_drop = 0;
+            } else if self.soft_drop > 0 {
+                self.soft_drop -= 1;
+                if self.soft_drop == 0 {
+                    // released where the drop said, not a row further
+                    self.fall = 0.0;
+                }
+            }
+        }
+
+        if self.game.board.piece_grounded(&self.game.active) {
+            self.lock_timer += 1;
+            if self.lock_timer >= self.config.lock_delay {
+                self.lock();
+            }
+        } else {
+            self.lock_timer = 0;
+        }
+    }
+
+    fn lock(&mut self) {
+        let placement = self.game.board.ghost(&self.game.active);
+        let mut drop = HardDrop::new();
+        drop.execute(&mut self.game);
+        let lines_cleared = drop.lines_cleared();
+
+        self.locks.push(Lock {
+            frame: self.frame,
+            placement,
+            lines_cleared,
+        });
+        self.delay = self.config.are;
+        if lines_cleared > 0 {
+            self.delay += self.config.line_clear_delay;
+        }
+        self.reset_piece();
+    }
+
+    fn reset_piece(&mut self) {
+        self.fall = 0.0;
+        self.soft_drop = 0;
+        self.das_shift = 0;
+        self.lock_timer = 0;
+        self.resets = 0;
+    }
+}
+
+/// Lays the inputs of every step of `plan` out `gap` frames apart. The
+/// simulator pushes them back further whenever the game is not ready.
+pub fn schedule(plan: &Plan, gap: u32) -> Option<Vec<TimedInput>> {
+    let keys = Key::from_commands(plan.steps.iter().flat_map(|step| step.inputs.iter()))?;
+    Some(
+        keys.into_iter()
+            .enumerate()
+            .map(|(i, key)| TimedInput {
+                frame: i as u32 * gap,
+                key,
+            })
+            .collect(),
+    )
+}
+
+/// Plays `plan` from `game` in real time and checks every piece locks where
+/// the plan put it. Returns the finished simulation for its press and lock logs.
+pub fn validate(
+    game: Game,
+    plan: &Plan,
+    config: TimingConfig,
+    max_frames: u32,
+) -> Result<Simulator, TimingError> {
+    let untypeable = |step: &Step| match Key::from_commands(step.inputs.iter()) {
+        None => true,
+        Some(keys) => keys.contains(&Key::SonicDrop) && !config.instant_soft_drop(),
+    };
+    if let Some(step) = plan.steps.iter().position(untypeable) {
+        return Err(TimingError::Untypeable { step });
+    }
+    let mut sim = Simulator::new(game, config);
+    sim.feed(schedule(plan, 1).unwrap());
+    sim.run(max_frames);
+
+    for (step, (planned, lock)) in plan.steps.iter().zip(sim.locks.iter()).enumerate() {
+        if planned.placement.abs_locations() != lock.placement.abs_locations() {
+            return Err(TimingError::Diverged {
+                step,
+                frame: lock.frame,
+                expected: planned.placement,
+                actual: lock.placement,
+            });
+        }
+    }
+    if sim.locks.len() < plan.steps.len() {
+        return Err(TimingError::Unfinished {
+            locked: sim.locks.len(),
+            expected: plan.steps.len(),
+        });
+    }
+    Ok(sim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one-piece plan that plays `keys` from `game`, landing wherever they
+    /// land with no gravity or lock delay in the way.
+    fn plan(game: &Game, keys: &[Key]) -> Plan {
+        let mut instant = game.clone();
+        for key in keys.iter().filter(|&&key| key != Key::HardDrop) {
+            key.command().execute(&mut instant);
+        }
+        let placement = instant.board.ghost(&instant.active);
+        HardDrop::new().execute(&mut instant);
+        Plan {
+            steps: vec![Step {
+                placement,
+                inputs: keys.iter().map(|&key| key.command()).collect(),
+                hold: false,
+                lines_cleared: 0,
+                board: instant.board,
+            }],
+            evaluation: 0,
+        }
+    }
+
+    #[test]
+    fn soft_drop_then_tuck_on_a_tall_board() {
+        let game = Game::new(40, 10, 1);
+        let plan = plan(&game, &[Key::SoftDrop, Key::Right, Key::HardDrop]);
+        let sim = validate(game, &plan, TimingConfig::default(), 10_000).unwrap();
+        assert_eq!(sim.locks.len(), 1);
+        // the drop took its time, and the tuck still came before the lock
+        assert!(sim.presses[0].frames > TimingConfig::default().lock_delay);
+    }
+
+    #[test]
+    fn partial_soft_drop_stops_where_released() {
+        let game = Game::new(40, 10, 1);
+        let plan = plan(
+            &game,
+            &[
+                Key::SoftDropBy(10),
+                Key::Cw,
+                Key::SoftDrop,
+                Key::Left,
+                Key::HardDrop,
+            ],
+        );
+        assert!(validate(game, &plan, TimingConfig::default(), 10_000).is_ok());
+    }
+
+    #[test]
+    fn lock_delay_beats_a_slow_tuck() {
+        let game = Game::new(20, 10, 1);
+        let plan = plan(&game, &[Key::SoftDrop, Key::Right, Key::HardDrop]);
+        let config = TimingConfig {
+            lock_delay: 1,
+            move_resets: 0,
+            das: 100,
+            ..TimingConfig::default()
+        };
+        let mut sim = Simulator::new(game.clone(), config);
+        // the tuck arrives long after the piece hit the floor
+        sim.feed([
+            TimedInput {
+                frame: 0,
+                key: Key::SoftDrop,
+            },
+            TimedInput {
+                frame: 500,
+                key: Key::Right,
+            },
+            TimedInput {
+                frame: 501,
+                key: Key::HardDrop,
+            },
+        ]);
+        sim.run(1_000);
+        assert_ne!(
+            sim.locks[0].placement.abs_locations(),
+            plan.steps[0].placement.abs_locations()
+        );
+    }
+
+    #[test]
+    fn pieces_fall_while_das_charges() {
+        // a tower in the two leftmost columns, which an instant shift to the
+        // wall would clear but a piece falling through DAS runs into
+        let mut game = Game::new(20, 10, 1);
+        game.board
+            .bulk_add((0..12).flat_map(|row| [(row, 0), (row, 1)]).collect());
+        let plan = plan(&game, &[Key::DasLeft, Key::HardDrop]);
+        let planned = plan.steps[0].placement;
+        assert_eq!(planned.abs_locations()[0], [12, 0]);
+
+        let fast = TimingConfig {
+            gravity: 1.0,
+            ..TimingConfig::default()
+        };
+        match validate(game.clone(), &plan, fast, 1_000) {
+            // it got one column further before the tower was in the way
+            Err(TimingError::Diverged { actual, .. }) => {
+                assert_eq!(actual.abs_locations()[0], [0, 2]);
+            }
+            other => panic!("{:?}", other.map(|sim| sim.locks)),
+        }
+
+        let config = TimingConfig::default();
+        let columns = (game.active.col - planned.col) as u32;
+        let sim = validate(game, &plan, config, 1_000).unwrap();
+        // one column on the press, then the rest once DAS is up
+        assert_eq!(
+            sim.presses[0].frames,
+            config.das + config.arr * (columns - 2) + 1
+        );
+    }
+
+    #[test]
+    fn sonic_drop_needs_instant_soft_drop() {
+        let game = Game::new(20, 10, 1);
+        let plan = plan(&game, &[Key::SonicDrop, Key::Right, Key::HardDrop]);
+        assert_eq!(
+            validate(game.clone(), &plan, TimingConfig::default(), 1_000).err(),
+            Some(TimingError::Untypeable { step: 0 })
+        );
+
+        let config = TimingConfig {
+            soft_drop_factor: 1000.0,
+            ..TimingConfig::default()
+        };
+        let sim = validate(game, &plan, config, 1_000).unwrap();
+        assert_eq!(sim.presses[0].frames, 1);
+    }
+
+    #[test]
+    fn engine_only_inputs_are_reported() {
+        let game = Game::new(20, 10, 1);
+        let mut plan = plan(&game, &[Key::HardDrop]);
+        plan.steps[0].inputs.insert(0, SetPiece::new().into());
+        assert_eq!(
+            validate(game, &plan, TimingConfig::default(), 1_000).err(),
+            Some(TimingError::Untypeable { step: 0 })
+        );
+    }
+}
@@ -0,0 +1,233 @@
+#![allow(dead_code)]
+
+use crate::finesse::Key;
+use crate::game::Game;
+use crate::piece::piece_name;
+use crate::plan::Plan;
+use crate::timing::{validate, Simulator, TimingConfig, TimingError};
+use serde_json::{json, Value};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Singleplayer replays in the shape TETR.IO saves as .ttr: a `start` and a
+// `full` event carrying the options, then one keydown and keyup per press
+// and an `end` event. The client deals its own pieces from `seed`, so the
+// pieces we played are also written out as `queue`, active piece first.
+
+#[derive(Debug)]
+pub enum ExportError {
+    /// the plan does not survive the timing rules, so the client would not
+    /// replay it either
+    Timing(TimingError),
+    Io(io::Error),
+}
+
+impl From<io::Error> for ExportError {
+    fn from(error: io::Error) -> Self {
+        ExportError::Io(error)
+    }
+}
+
+fn key_name(key: Key) -> &'static str {
+    match key {
+        Key::Left | Key::DasLeft => "moveLeft",
+        Key::Right | Key::DasRight => "moveRight",
+        Key::Cw => "rotateCW",
+        Key::Ccw => "rotateCCW",
+        Key::Flip => "rotate180",
+        // the client has no sonic drop; `validate` only lets one through
+        // when soft drop is instant, so one frame of soft drop does it
+        Key::SoftDrop | Key::SoftDropBy(_) | Key::SonicDrop => "softDrop",
+        Key::HardDrop => "hardDrop",
+        Key::Hold => "hold",
+    }
+}
+
+fn key_event(frame: u32, kind: &str, key: Key) -> Value {
+    json!({
+        "frame": frame,
+        "type": kind,
+        "data": { "key": key_name(key), "subframe": 0.0 },
+    })
+}
+
+// f32 -> f64 turns 0.02 into 0.0199999995..., which is only noise in a replay
+fn decimal(value: f32) -> f64 {
+    (value as f64 * 1e6).round() / 1e6
+}
+
+/// The active piece of `start` and the next `count` pieces after it.
+pub fn dealt(start: &Game, count: usize) -> String {
+    let mut queue = start.queue.clone();
+    let next = (0..count).map(|_| queue.next());
+    std::iter::once(start.active.piece_type)
+        .chain(next)
+        .map(piece_name)
+        .collect()
+}
+
+fn options(sim: &Simulator, seed: usize, queue: &str) -> Value {
+    let config = &sim.config;
+    json!({
+        "seed": seed,
+        "queue": queue,
+        "boardwidth": sim.game.board.width,
+        "boardheight": sim.game.board.height,
+        "bagtype": "7-bag",
+        "spinbonuses": "T-spins",
+        "allow180": true,
+        "g": decimal(config.gravity),
+        "locktime": config.lock_delay,
+        "lockresets": config.move_resets,
+        "are": config.are,
+        "lineclear_are": config.line_clear_delay,
+        "handling": {
+            "das": config.das,
+            "arr": config.arr,
+            "sdf": decimal(config.soft_drop_factor),
+            "dcd": 0,
+            "safelock": false,
+        },
+    })
+}
+
+/// The replay of a finished simulation, with its presses as key events.
+/// `queue` lists the pieces it was dealt, as `dealt` writes them.
+pub fn to_ttr(sim: &Simulator, seed: usize, queue: &str, username: &str) -> Value {
+    let options = options(sim, seed, queue);
+    let mut events = vec![
+        json!({ "frame": 0, "type": "start", "data": {} }),
+        json!({ "frame": 0, "type": "full", "data": { "options": options } }),
+    ];
+    let mut keys: Vec<(u32, u8, Value)> = Vec::new();
+    for press in sim.presses.iter() {
+        // keyups sort before keydowns on the same frame, so a key released
+        // and pressed again on one frame comes out in that order
+        keys.push((press.frame, 1, key_event(press.frame, "keydown", press.key)));
+        let up = press.frame + press.frames;
+        keys.push((up, 0, key_event(up, "keyup", press.key)));
+    }
+    keys.sort_by_key(|&(frame, order, _)| (frame, order));
+    events.extend(keys.into_iter().map(|(_, _, event)| event));
+
+    let frames = sim.frame.max(
+        events
+            .last()
+            .map_or(0, |event| event["frame"].as_u64().unwrap_or(0) as u32),
+    );
+    events.push(json!({ "frame": frames, "type": "end", "data": {} }));
+
+    let lines: usize = sim.locks.iter().map(|lock| lock.lines_cleared).sum();
+    json!({
+        "ismulti": false,
+        "endcontext": {
+            "username": username,
+            "piecesplaced": sim.locks.len(),
+            "lines": lines,
+            "finalTime": frames as f64 * 1000.0 / 60.0,
+        },
+        "data": [{
+            "board": [{ "username": username, "active": true, "success": true }],
+            "replays": [{ "frames": frames, "events": events }],
+        }],
+    })
+}
+
+/// Plays `plan` from `game` under `config` and, if every piece lands where
+/// planned, returns it as a replay. `seed` must be the one `game` was made
+/// with; finesse-refined plans give the shortest replays.
+pub fn export(
+    game: Game,
+    seed: usize,
+    plan: &Plan,
+    config: TimingConfig,
+    username: &str,
+) -> Result<Value, ExportError> {
+    // generous: a whole lock delay per key is far more than any plan needs
+    let keys: usize = plan.steps.iter().map(|step| step.inputs.len() + 1).sum();
+    let max_frames = (keys as u32 + 1)
+        * (config.lock_delay + config.das + config.are + config.line_clear_delay + 1);
+    // every lock deals one piece, and a hold may deal one more
+    let queue = dealt(&game, plan.steps.len() + 1);
+    let sim = validate(game, plan, config, max_frames).map_err(ExportError::Timing)?;
+    Ok(to_ttr(&sim, seed, &queue, username))
+}
+
+pub fn write_ttr<P: AsRef<Path>>(replay: &Value, path: P) -> Result<(), ExportError> {
+    fs::write(path, replay.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::{Executable, HardDrop};
+    use crate::plan::Step;
+
+    /// One step per key list, each landing wherever its keys take it.
+    fn plan(game: &Game, pieces: &[&[Key]]) -> Plan {
+        let mut game = game.clone();
+        let steps = pieces
+            .iter()
+            .map(|keys| {
+                for key in keys.iter().filter(|&&key| key != Key::HardDrop) {
+                    key.command().execute(&mut game);
+                }
+                let placement = game.board.ghost(&game.active);
+                HardDrop::new().execute(&mut game);
+                Step {
+                    placement,
+                    inputs: keys.iter().map(|&key| key.command()).collect(),
+                    hold: false,
+                    lines_cleared: 0,
+                    board: game.board.clone(),
+                }
+            })
+            .collect();
+        Plan {
+            steps,
+            evaluation: 0,
+        }
+    }
+
+    fn events(replay: &Value) -> &Vec<Value> {
+        replay["data"][0]["replays"][0]["events"]
+            .as_array()
+            .unwrap()
+    }
+
+    #[test]
+    fn writes_the_pieces_played() {
+        let game = Game::new(20, 10, 1);
+        let plan = plan(&game, &[&[Key::DasLeft, Key::HardDrop], &[Key::HardDrop]]);
+        let replay = export(game.clone(), 1, &plan, TimingConfig::default(), "bot").unwrap();
+
+        let options = &events(&replay)[1]["data"]["options"];
+        assert_eq!(options["queue"], "OJIL");
+        assert_eq!(replay["endcontext"]["piecesplaced"], 2);
+
+        let events = events(&replay);
+        assert_eq!(events.last().unwrap()["type"], "end");
+        let downs = events.iter().filter(|event| event["type"] == "keydown");
+        let ups = events.iter().filter(|event| event["type"] == "keyup");
+        assert_eq!((downs.count(), ups.count()), (3, 3));
+    }
+
+    #[test]
+    fn soft_drop_is_held_down() {
+        let game = Game::new(40, 10, 1);
+        let plan = plan(&game, &[&[Key::SoftDrop, Key::Right, Key::HardDrop]]);
+        let replay = export(game, 1, &plan, TimingConfig::default(), "bot").unwrap();
+
+        let frame = |kind: &str| {
+            events(&replay)
+                .iter()
+                .find(|event| event["type"] == kind && event["data"]["key"] == "softDrop")
+                .unwrap()["frame"]
+                .as_u64()
+                .unwrap()
+        };
+        assert!(frame("keyup") - frame("keydown") > 1);
+    }
+}
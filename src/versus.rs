@@ -0,0 +1,342 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+use crate::bot::Bot;
+use crate::garbage::{attack, GarbageConfig, GarbageGenerator};
+use crate::plan::Step;
+use std::fmt::{Display, Formatter};
+
+/// Costs for the shape a step leaves behind; lower totals are better, so
+/// `attack` is subtracted.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Weights {
+    /// summed column heights
+    pub height: i32,
+    /// the tallest column
+    pub max_height: i32,
+    /// empty cells with something above them
+    pub holes: i32,
+    /// summed height differences between neighbouring columns
+    pub bumpiness: i32,
+    /// per line of attack sent
+    pub attack: i32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            height: 2,
+            max_height: 4,
+            holes: 30,
+            bumpiness: 3,
+            attack: 40,
+        }
+    }
+}
+
+fn heights(board: &Board) -> Vec<usize> {
+    (0..board.width)
+        .map(|col| {
+            (0..board.height)
+                .rev()
+                .find(|&row| board.get(row, col))
+                .map_or(0, |row| row + 1)
+        })
+        .collect()
+}
+
+impl Weights {
+    pub fn evaluate(&self, step: &Step) -> i32 {
+        let board = &step.board;
+        let heights = heights(board);
+        let holes: usize = heights
+            .iter()
+            .enumerate()
+            .map(|(col, &height)| (0..height).filter(|&row| !board.get(row, col)).count())
+            .sum();
+        let bumpiness: usize = heights
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum();
+        let sent = attack(
+            step.lines_cleared,
+            step.lines_cleared > 0 && board.is_empty(),
+        );
+
+        self.height * heights.iter().sum::<usize>() as i32
+            + self.max_height * heights.iter().copied().max().unwrap_or(0) as i32
+            + self.holes * holes as i32
+            + self.bumpiness * bumpiness as i32
+            - self.attack * sent as i32
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct VersusConfig {
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    pub candidates: usize,
+    /// pieces an attack spends in flight, during which it can still be
+    /// cancelled, before it joins the receiver's pending garbage
+    pub garbage_delay: usize,
+    pub garbage: GarbageConfig,
+    /// pieces each side places before the match is called a draw
+    pub max_pieces: usize,
+}
+
+impl Default for VersusConfig {
+    fn default() -> Self {
+        Self {
+            width: 10,
+            height: 20,
+            depth: 1,
+            candidates: 5,
+            garbage_delay: 1,
+            garbage: GarbageConfig::clean(),
+            max_pieces: 300,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MatchResult {
+    /// 0 or 1, or `None` for a draw
+    pub winner: Option<usize>,
+    pub pieces: usize,
+    pub attack: [usize; 2],
+}
+
+struct Attack {
+    lands: usize,
+    lines: usize,
+}
+
+/// One side of a match: a bot, how it judges positions, and the attacks
+/// heading its way.
+struct Side {
+    bot: Bot,
+    weights: Weights,
+    garbage: GarbageGenerator,
+    incoming: Vec<Attack>,
+}
+
+impl Side {
+    /// Plays one piece and returns the attack it sent, or `None` once it
+    /// has nowhere left to go.
+    fn turn(&mut self, config: &VersusConfig) -> Option<usize> {
+        let weights = self.weights;
+        let plan = self
+            .bot
+            .best_plan_by(config.depth, config.candidates, &|step| {
+                weights.evaluate(step)
+            })?;
+        let before = self.bot.game.attack_sent;
+        self.bot.play(plan.first()?);
+        if self.bot.game.topped_out() {
+            return None;
+        }
+
+        // whatever pending garbage didn't soak up cancels what's in flight
+        let mut sent = self.bot.game.attack_sent - before;
+        for attack in self.incoming.iter_mut() {
+            let cancel = sent.min(attack.lines);
+            attack.lines -= cancel;
+            sent -= cancel;
+        }
+        self.incoming.retain(|attack| attack.lines > 0);
+        Some(sent)
+    }
+
+    fn land(&mut self, piece: usize, width: usize) {
+        let (landed, flying) = self
+            .incoming
+            .drain(..)
+            .partition(|attack| attack.lands <= piece);
+        self.incoming = flying;
+        for attack in landed {
+            let holes = self.garbage.holes(attack.lines, width);
+            self.bot.game.receive_garbage(holes);
+        }
+    }
+}
+
+/// Plays `a` against `b` on the same piece sequence, one piece each in turn,
+/// with odd seeds letting `b` go first. Any seed works, 0 included.
+pub fn play_match(seed: usize, a: Weights, b: Weights, config: &VersusConfig) -> MatchResult {
+    let side = |weights, garbage_seed| Side {
+        bot: Bot::new(config.height, config.width, seed),
+        weights,
+        garbage: GarbageGenerator::new(garbage_seed, config.garbage),
+        incoming: Vec::new(),
+    };
+    let mut sides = [side(a, seed * 2 + 1), side(b, seed * 2 + 2)];
+    let mut attack = [0; 2];
+
+    let order = if seed.is_multiple_of(2) {
+        [0, 1]
+    } else {
+        [1, 0]
+    };
+    for piece in 0..config.max_pieces {
+        for player in order {
+            sides[player].land(piece, config.width);
+            let Some(sent) = sides[player].turn(config) else {
+                return MatchResult {
+                    winner: Some(1 - player),
+                    pieces: piece,
+                    attack,
+                };
+            };
+            if sent > 0 {
+                attack[player] += sent;
+                sides[1 - player].incoming.push(Attack {
+                    lands: piece + config.garbage_delay,
+                    lines: sent,
+                });
+            }
+        }
+    }
+    MatchResult {
+        winner: None,
+        pieces: config.max_pieces,
+        attack,
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Stats {
+    pub games: usize,
+    pub wins: [usize; 2],
+    pub draws: usize,
+    pub pieces: usize,
+}
+
+impl Stats {
+    pub fn add(&mut self, result: &MatchResult) {
+        self.games += 1;
+        self.pieces += result.pieces;
+        match result.winner {
+            Some(player) => self.wins[player] += 1,
+            None => self.draws += 1,
+        }
+    }
+
+    pub fn win_rate(&self, player: usize) -> f32 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.wins[player] as f32 / self.games as f32
+        }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} games: {} - {} ({} drawn), win rates {:.3} / {:.3}, {:.1} pieces per game",
+            self.games,
+            self.wins[0],
+            self.wins[1],
+            self.draws,
+            self.win_rate(0),
+            self.win_rate(1),
+            self.pieces as f32 / self.games.max(1) as f32
+        )
+    }
+}
+
+/// Plays one match per seed and tallies the results.
+pub fn run_matches(
+    seeds: impl IntoIterator<Item = usize>,
+    a: Weights,
+    b: Weights,
+    config: &VersusConfig,
+) -> Stats {
+    let mut stats = Stats::default();
+    for seed in seeds {
+        stats.add(&play_match(seed, a, b, config));
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbage::PERFECT_CLEAR_BONUS;
+    use crate::piece::Placement;
+
+    fn step(board: Board, lines_cleared: usize) -> Step {
+        Step {
+            placement: Placement::default(),
+            inputs: Vec::new(),
+            hold: false,
+            lines_cleared,
+            board,
+        }
+    }
+
+    #[test]
+    fn weights_count_the_shape() {
+        let mut board = Board::new(4, 6);
+        // heights 3, 0, 1, 0 with a hole under the first column
+        board.bulk_add(vec![(0, 0), (2, 0), (0, 2)]);
+        let weights = Weights {
+            height: 1,
+            max_height: 10,
+            holes: 100,
+            bumpiness: 1000,
+            attack: 0,
+        };
+        assert_eq!(weights.evaluate(&step(board, 0)), 4 + 30 + 100 + 5000);
+    }
+
+    #[test]
+    fn clears_earn_attack() {
+        let mut board = Board::new(4, 6);
+        board.add(0, 0);
+        let weights = Weights::default();
+        let quiet = weights.evaluate(&step(board.clone(), 0));
+        assert_eq!(
+            weights.evaluate(&step(board, 4)),
+            quiet - 4 * weights.attack
+        );
+        let perfect = weights.evaluate(&step(Board::new(4, 6), 1));
+        assert_eq!(perfect, -(PERFECT_CLEAR_BONUS as i32) * weights.attack);
+    }
+
+    #[test]
+    fn matches_are_reproducible() {
+        let config = VersusConfig {
+            max_pieces: 30,
+            ..VersusConfig::default()
+        };
+        let weights = Weights::default();
+        for seed in [0, 1] {
+            let result = play_match(seed, weights, weights, &config);
+            assert_eq!(result, play_match(seed, weights, weights, &config));
+            assert!(result.pieces <= config.max_pieces);
+        }
+    }
+
+    #[test]
+    fn a_bot_that_loves_holes_loses() {
+        let config = VersusConfig {
+            height: 12,
+            max_pieces: 200,
+            garbage_delay: 0,
+            ..VersusConfig::default()
+        };
+        let reckless = Weights {
+            height: -5,
+            holes: -30,
+            ..Weights::default()
+        };
+        let stats = run_matches(1..=2, Weights::default(), reckless, &config);
+        assert_eq!(stats.games, 2);
+        assert_eq!(stats.wins[0], 2);
+        assert_eq!(stats.win_rate(0), 1.0);
+    }
+}
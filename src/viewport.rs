@@ -0,0 +1,113 @@
+#![allow(dead_code)]
+
+use crate::board::Board;
+
+/// Rows kept free over the picture by default: enough for a piece to spawn
+/// at `height - 3` and turn without touching it.
+pub const BUFFER_ROWS: usize = 4;
+
+/// Where a target sits on a playfield taller than it, `row` and `col`
+/// being its bottom left cell. The rows above are left for pieces to spawn
+/// and move around in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Viewport {
+    pub row: usize,
+    pub col: usize,
+    pub height: usize,
+    pub width: usize,
+}
+
+impl Viewport {
+    pub fn new(row: usize, col: usize, height: usize, width: usize) -> Self {
+        Self {
+            row,
+            col,
+            height,
+            width,
+        }
+    }
+
+    /// A viewport the size of `target` in the bottom left corner.
+    pub fn bottom(target: &Board) -> Self {
+        Self::new(0, 0, target.height, target.width)
+    }
+
+    /// The smallest playfield holding the viewport with `buffer` rows over
+    /// it, as `(height, width)` in the order `Bot::new` takes them.
+    pub fn field(&self, buffer: usize) -> (usize, usize) {
+        (self.row + self.height + buffer, self.col + self.width)
+    }
+
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        (self.row..self.row + self.height).contains(&row)
+            && (self.col..self.col + self.width).contains(&col)
+    }
+
+    /// `target` drawn into an empty board the size of `field`, so it can be
+    /// scored against pieces on the playfield.
+    pub fn place(&self, target: &Board, field: &Board) -> Board {
+        let mut out = Board::new(field.width, field.height);
+        for row in 0..self.height.min(target.height) {
+            for col in 0..self.width.min(target.width) {
+                if target.get(row, col) {
+                    out.add(self.row + row, self.col + col);
+                }
+            }
+        }
+        out
+    }
+
+    /// The part of `board` inside the viewport, the same size as a target.
+    pub fn crop(&self, board: &Board) -> Board {
+        let mut out = Board::new(self.width, self.height);
+        for row in 0..self.height {
+            for col in 0..self.width {
+                if let Some(kind) = board.kind(self.row + row, self.col + col) {
+                    out.paint(row, col, kind);
+                } else if board.get(self.row + row, self.col + col) {
+                    out.add(row, col);
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_fits_the_viewport_and_buffer() {
+        let viewport = Viewport::new(2, 1, 6, 8);
+        assert_eq!(viewport.field(BUFFER_ROWS), (12, 9));
+        assert!(viewport.contains(2, 1) && viewport.contains(7, 8));
+        assert!(!viewport.contains(1, 1) && !viewport.contains(8, 1) && !viewport.contains(2, 9));
+    }
+
+    #[test]
+    fn place_and_crop_round_trip() {
+        let mut target = Board::new(4, 3);
+        target.bulk_add(vec![(0, 0), (1, 2), (2, 3)]);
+        let viewport = Viewport::new(1, 2, 3, 4);
+        let (height, width) = viewport.field(BUFFER_ROWS);
+        let field = Board::new(width, height);
+
+        let placed = viewport.place(&target, &field);
+        assert_eq!((placed.width, placed.height), (6, 8));
+        assert!(placed.get(1, 2) && placed.get(2, 4) && placed.get(3, 5));
+        assert_eq!(placed.arr.iter().flatten().filter(|&&cell| cell).count(), 3);
+        assert_eq!(viewport.crop(&placed), target);
+    }
+
+    #[test]
+    fn crop_keeps_piece_kinds() {
+        let mut board = Board::new(10, 20);
+        board.paint(0, 0, 6);
+        board.add(0, 1);
+        let cropped = Viewport::new(0, 0, 2, 2).crop(&board);
+        assert_eq!(cropped.kind(0, 0), Some(6));
+        assert!(cropped.get(0, 1) && cropped.kind(0, 1).is_none());
+    }
+}